@@ -1,35 +1,80 @@
 use gdnative::prelude::*;
-use gdnative::api::{TileMap, InputEvent, InputEventMouseMotion, InputEventMouseButton};
+use gdnative::api::{
+    ButtonGroup, Font, InputEvent, InputEventKey, InputEventMouseButton, InputEventMouseMotion,
+    Label, TileMap, VisualServer,
+};
 use gdnative::api::GlobalConstants;
-use super_prime_mover::{Board, Tile, Orientation, Connection};
+use super_prime_mover::{Board, Game, Orientation, Signal, Tile, Verdict};
 use bit_field::*;
 
+mod palette;
+
+use palette::{Cell, PaletteEntry, PALETTE};
+
 #[derive(NativeClass)]
 #[inherit(TileMap)]
 pub struct GridTileMap {
     last_hover: Option<Vector2>,
     last_drag: Option<Vector2>,
+    /// The tile under the mouse, whatever is on it.
+    cursor: Option<Vector2>,
     board: Board,
-    tile_kind: TileKind
+    /// What a left click, and the drag following it, does.
+    tool: Tool,
+    /// The game being simulated, if any. The board can't be edited until it
+    /// gets reset.
+    game: Option<Game>,
+    running: bool,
+    /// Ticks per second while running.
+    tick_rate: f64,
+    /// Seconds since the last tick.
+    elapsed: f64,
+    /// The font signal values are drawn with.
+    font: Option<Ref<Font>>,
+    /// Where signals get drawn. The TileMap's own drawing goes below its
+    /// tiles, this goes above.
+    signal_layer: Option<Rid>,
+}
+
+/// An editing tool, picked from the toolbar.
+#[derive(Clone, Copy)]
+enum Tool {
+    /// Places a palette entry's tile, then wires up the tiles dragged over,
+    /// laying cables on empty ones. With shift held, it disconnects instead.
+    Place(&'static PaletteEntry),
+    /// Clears the tiles clicked or dragged over.
+    Erase,
+    /// Cuts the connections dragged across.
+    Disconnect,
+    /// Turns the directional tile clicked.
+    Rotate,
 }
 
+/// The tools besides the palette entries, with their button label.
+const EDIT_TOOLS: [(&str, Tool); 3] = [
+    ("Erase", Tool::Erase),
+    ("Disconnect", Tool::Disconnect),
+    ("Rotate", Tool::Rotate),
+];
+
 const CLEAR: i64 = -1;
-const CABLE_W : i64 = 0;
-const CABLE_E : i64 = 1;
-const CABLE_H : i64 = 2;
-const CABLE_NE : i64 = 3;
-const CABLE_NW : i64 = 4;
-const CABLE_N : i64 = 5;
-const CABLE_SE : i64 = 6;
-const CABLE_SW : i64 = 7;
-const CABLE_S : i64 = 8;
-const CABLE_UNCONNECTED : i64 = 9;
-const CABLE_V: i64 = 10;
-const IO_E: i64 = 11;
-const IO_S: i64 = 12;
-const IO_N: i64 = 13;
-const IO_W: i64 = 14;
-const IO_UNCONNECTED: i64 = 15;
+
+const DEFAULT_TICK_RATE: f64 = 4.0;
+/// How many ticks a game runs for before giving up on it.
+const MAX_TICKS: u64 = 100_000;
+
+const SIGNAL_COLOR: Color = Color {
+    r: 0.9,
+    g: 0.3,
+    b: 0.2,
+    a: 1.0,
+};
+const VALUE_COLOR: Color = Color {
+    r: 1.0,
+    g: 1.0,
+    b: 1.0,
+    a: 1.0,
+};
 
 #[methods]
 impl GridTileMap {
@@ -37,64 +82,167 @@ impl GridTileMap {
         GridTileMap {
             last_hover: None,
             last_drag: None,
+            cursor: None,
             board: Board::default(),
-            tile_kind: TileKind::WIRE,
+            tool: Tool::Place(&PALETTE[0]),
+            game: None,
+            running: false,
+            tick_rate: DEFAULT_TICK_RATE,
+            elapsed: 0.0,
+            font: None,
+            signal_layer: None,
+        }
+    }
+
+    #[export]
+    fn _ready(&mut self, owner: &TileMap) {
+        // A TileMap has no theme, so borrow the default font from a control.
+        let label = Label::new();
+        self.font = label.get_font("font", "");
+        label.free();
+
+        let visual_server = unsafe { VisualServer::godot_singleton() };
+        let layer = visual_server.canvas_item_create();
+        visual_server.canvas_item_set_parent(layer, owner.get_canvas_item());
+        visual_server.canvas_item_set_z_index(layer, 1);
+        self.signal_layer = Some(layer);
+
+        // One tool button per palette entry, then the other tools. Only one
+        // of them is pressed at a time: the one for the current tool.
+        let tools = owner.get_node("../GUI/MarginContainer2/Tool");
+        if let Some(tools) = tools.map(|tools| unsafe { tools.assume_safe() }) {
+            let loader = ResourceLoader::godot_singleton();
+            let group = ButtonGroup::new().into_shared();
+            for (i, entry) in PALETTE.iter().enumerate() {
+                // The first entry is the tool to start with.
+                let button = tool_button(owner, &group, entry.name, "_change_tile", i, i == 0);
+                let icon = loader
+                    .load(entry.icon, "Texture", false)
+                    .and_then(|icon| icon.cast::<Texture>());
+                if let Some(icon) = icon {
+                    button.set_button_icon(icon);
+                }
+                tools.add_child(button, false);
+            }
+            for (i, (name, _)) in EDIT_TOOLS.iter().enumerate() {
+                tools.add_child(
+                    tool_button(owner, &group, name, "_change_tool", i, false),
+                    false,
+                );
+            }
+        }
+    }
+
+    #[export]
+    fn _exit_tree(&mut self, _owner: &TileMap) {
+        if let Some(layer) = self.signal_layer.take() {
+            unsafe { VisualServer::godot_singleton() }.free_rid(layer);
         }
     }
 
     fn redraw_board(&self, owner: &TileMap) {
+        let board = self.shown_board();
         // TODO: Use an iterator
-        for y in 0..10usize {
-            for x in 0..10usize {
-                let tile = self.board.get_tile(x, y);
-                let [n, e, s, w] = self.board.get_connections(x, y);
-                let x = x as i64;
-                let y = y as i64;
-
-                const D: bool = false;
-                match (tile, n, e, s, w) {
-                    (Some(Tile::Empty), _, _, _, _) => {
-                        owner.set_cell(x, y, CLEAR, false, false, false, Vector2::zero());
-                        if let Some(v) = self.last_hover {
-                            if v.x as i64 == x && v.y as i64 == y {
-                                owner.set_cell(x, y, self.tile_kind.tile_num(), false, false, false, Vector2::zero());
-                            }
-                        }
-                    },
-                    (Some(Tile::Wire { .. }), D, D, D, D) => owner.set_cell(x, y, CABLE_UNCONNECTED, false, false, false, Vector2::zero()),
-                    (Some(Tile::Wire { .. }), _, D, D, D) => owner.set_cell(x, y, CABLE_N, false, false, false, Vector2::zero()),
-                    (Some(Tile::Wire { .. }), D, _, D, D) => owner.set_cell(x, y, CABLE_E, false, false, false, Vector2::zero()),
-                    (Some(Tile::Wire { .. }), D, D, _, D) => owner.set_cell(x, y, CABLE_S, false, false, false, Vector2::zero()),
-                    (Some(Tile::Wire { .. }), D, D, D, _) => owner.set_cell(x, y, CABLE_W, false, false, false, Vector2::zero()),
-                    (Some(Tile::Wire { .. }), _, _, D, D) => owner.set_cell(x, y, CABLE_NE, false, false, false, Vector2::zero()),
-                    (Some(Tile::Wire { .. }), _, D, _, D) => owner.set_cell(x, y, CABLE_V, false, false, false, Vector2::zero()),
-                    (Some(Tile::Wire { .. }), _, D, D, _) => owner.set_cell(x, y, CABLE_NW, false, false, false, Vector2::zero()),
-                    (Some(Tile::Wire { .. }), D, _, D, _) => owner.set_cell(x, y, CABLE_H, false, false, false, Vector2::zero()),
-                    (Some(Tile::Wire { .. }), D, _, _, D) => owner.set_cell(x, y, CABLE_SE, false, false, false, Vector2::zero()),
-                    (Some(Tile::Wire { .. }), D, D, _, _) => owner.set_cell(x, y, CABLE_SW, false, false, false, Vector2::zero()),
-                    (Some(Tile::Wire { .. }), _, _, _, _) => {
-                        // WTF??
-                        owner.set_cell(x, y, CLEAR, false, false, false, Vector2::zero());
-                        godot_print!("WEIRD: {}:{} = {:?}({},{},{},{})", x, y, tile, n, e, s, w);
-                    },
-                    (Some(Tile::Input { .. }), D, D, D, D) => owner.set_cell(x, y, IO_UNCONNECTED, false, false, false, Vector2::zero()),
-                    (Some(Tile::Input { .. }), _, D, D, D) => owner.set_cell(x, y, IO_N, false, false, false, Vector2::zero()),
-                    (Some(Tile::Input { .. }), D, _, D, D) => owner.set_cell(x, y, IO_E, false, false, false, Vector2::zero()),
-                    (Some(Tile::Input { .. }), D, D, _, D) => owner.set_cell(x, y, IO_S, false, false, false, Vector2::zero()),
-                    (Some(Tile::Input { .. }), D, D, D, _) => owner.set_cell(x, y, IO_W, false, false, false, Vector2::zero()),
-                    (Some(Tile::Input { .. }), _, _, _, _) => {
-                        // WTF??
-                        owner.set_cell(x, y, CLEAR, false, false, false, Vector2::zero());
-                        godot_print!("WEIRD: {}:{} = {:?}({},{},{},{})", x, y, tile, n, e, s, w);
-                    },
-                    _ => ()
-                }
+        for y in 0..board.height() {
+            for x in 0..board.width() {
+                self.draw_tile(owner, x, y);
+            }
+        }
+        self.draw_signals(owner);
+    }
+
+    /// The board on screen: the game's while there is one, since tiles like
+    /// locks and flipflops change as it runs.
+    fn shown_board(&self) -> &Board {
+        self.game.as_ref().map_or(&self.board, Game::board)
+    }
+
+    fn draw_tile(&self, owner: &TileMap, x: usize, y: usize) {
+        let board = self.shown_board();
+        let tile = match board.get_tile(x, y) {
+            Some(tile) => tile,
+            None => return,
+        };
+        let connections = board.get_connections(x, y);
+        let x = x as i64;
+        let y = y as i64;
+
+        if *tile == Tile::Empty {
+            let hovered = self
+                .last_hover
+                .map_or(false, |v| v.x as i64 == x && v.y as i64 == y);
+            let preview = match self.tool {
+                Tool::Place(entry) if hovered => entry.cells.preview(),
+                _ => None,
+            };
+            set_cell(owner, x, y, preview);
+            return;
+        }
+        let cell = palette::entry_for(tile)
+            .and_then(|entry| entry.cells.cell(connections, palette::orientation(tile)));
+        if cell.is_none() {
+            // WTF??
+            godot_print!("WEIRD: {}:{} = {:?}({:?})", x, y, tile, connections);
+        }
+        set_cell(owner, x, y, cell);
+    }
+
+    /// Draws the signals of the game over the board, along with their value.
+    /// While the game runs, they slide towards the tile they move to next.
+    fn draw_signals(&self, owner: &TileMap) {
+        let layer = match self.signal_layer {
+            Some(layer) => layer,
+            None => return,
+        };
+        let visual_server = unsafe { VisualServer::godot_singleton() };
+        visual_server.canvas_item_clear(layer);
+        let game = match &self.game {
+            Some(game) => game,
+            None => return,
+        };
+        let progress = if self.running {
+            (self.elapsed * self.tick_rate).min(1.0) as f32
+        } else {
+            0.0
+        };
+        let cell_size = owner.cell_size();
+        let radius = cell_size.x.min(cell_size.y) / 3.0;
+        for signal in game.signals() {
+            let (x, y) = signal.position();
+            let mut position = Vector2::new(x as f32, y as f32);
+            if will_move(game.board(), signal) {
+                let (dx, dy) = signal.direction().to_vector();
+                position += Vector2::new(dx as f32, dy as f32) * progress;
+            }
+            let center = owner.map_to_world(position, false) + cell_size / 2.0;
+            visual_server.canvas_item_add_circle(layer, center, radius as f64, SIGNAL_COLOR);
+
+            if let Some(font) = &self.font {
+                let font = unsafe { font.assume_safe() };
+                let text = signal.value().to_string();
+                let size = font.get_string_size(text.as_str());
+                // Strings are drawn from their baseline.
+                let baseline = font.get_ascent() as f32 - size.y / 2.0;
+                let at = center + Vector2::new(-size.x / 2.0, baseline);
+                font.draw(
+                    layer,
+                    at,
+                    text.as_str(),
+                    VALUE_COLOR,
+                    -1,
+                    Color::rgba(0.0, 0.0, 0.0, 0.0),
+                );
             }
         }
     }
 
     #[export]
     fn _input(&mut self, owner: &TileMap, event: Ref<InputEvent, Shared>) {
+        if self.game.is_some() {
+            // No editing until the game gets reset.
+            return;
+        }
+
         if let Some(event) = event.clone().cast::<InputEventMouseMotion>() {
             let event = unsafe { event.assume_safe() };
             let local_pos = owner
@@ -107,6 +255,7 @@ impl GridTileMap {
             let tile_hover_u = if let Some(v) = tile_hover.try_cast::<usize>() {
                 v
             } else { return };
+            self.cursor = Some(tile_hover);
 
             // First, remove the hover if the tile we're hovering changed
             match self.last_hover {
@@ -118,16 +267,38 @@ impl GridTileMap {
             }
 
             // If we're in the middle of a drag, insert a cable if necessary and
-            // connect the previous drag location to the current hover
+            // connect the previous drag location to the current hover. When
+            // disconnecting, the drag cuts the connections it crosses instead.
             if event.button_mask().get_bit(GlobalConstants::BUTTON_LEFT as usize - 1) {
-                if let Some(&Tile::Empty) = self.board.get_tile(tile_hover_u.x, tile_hover_u.y) {
-                    self.board.set_tile(tile_hover_u.x, tile_hover_u.y, Tile::Wire { slow: false });
-                    owner.set_cellv(tile_hover, CABLE_UNCONNECTED, false, false, false);
+                let (x, y) = (tile_hover_u.x, tile_hover_u.y);
+                let tool = match self.tool {
+                    Tool::Place(_) if event.shift() => Tool::Disconnect,
+                    tool => tool,
+                };
+                let occupied =
+                    matches!(self.board.get_tile(x, y), Some(tile) if *tile != Tile::Empty);
+                match tool {
+                    Tool::Place(_) if self.board.get_tile(x, y) == Some(&Tile::Empty) => {
+                        self.board.set_tile(x, y, Tile::Wire { slow: false });
+                        self.draw_tile(owner, x, y);
+                    }
+                    Tool::Erase if occupied => {
+                        self.board.clear_tile(x, y);
+                        self.redraw_board(owner);
+                    }
+                    _ => (),
                 }
-                self.last_drag.and_then(|v| {
+                let cutting = matches!(tool, Tool::Disconnect);
+                let wiring = cutting || matches!(tool, Tool::Place(_));
+                self.last_drag.filter(|_| wiring).and_then(|v| {
                     let orientation = adjascency(v, tile_hover)?;
-                    godot_print!("Connecting {:?} to {:?}", v, orientation);
-                    self.board.connect(v.x as usize, v.y as usize, orientation);
+                    if cutting {
+                        self.board
+                            .disconnect(v.x as usize, v.y as usize, orientation);
+                    } else {
+                        godot_print!("Connecting {:?} to {:?}", v, orientation);
+                        self.board.connect(v.x as usize, v.y as usize, orientation);
+                    }
                     self.redraw_board(owner);
                     Some(())
                 });
@@ -136,8 +307,8 @@ impl GridTileMap {
 
             if let Some(&Tile::Empty) = self.board.get_tile(tile_hover_u.x, tile_hover_u.y) {
                 // TODO: Transparency to signal that it's just a hover.
-                owner.set_cellv(tile_hover, self.tile_kind.tile_num(), false, false, false);
                 self.last_hover = Some(tile_hover);
+                self.draw_tile(owner, tile_hover_u.x, tile_hover_u.y);
             }
         }
 
@@ -154,23 +325,235 @@ impl GridTileMap {
                 v
             } else { return };
             match event.button_index() {
+                GlobalConstants::BUTTON_LEFT if event.is_pressed() => {
+                    // Everything done until the button is released, the drag
+                    // included, gets undone at once.
+                    self.board.begin_group();
+                    let (x, y) = (tile_hover_u.x, tile_hover_u.y);
+                    match self.tool {
+                        // Only tiles get placed: other tools, and dragging
+                        // with shift held, leave the clicked tile alone.
+                        Tool::Place(entry) if !event.shift() => {
+                            let tile = (entry.new_tile)(&mut self.board);
+                            self.board.set_tile(x, y, tile);
+                            self.draw_tile(owner, x, y);
+                        }
+                        Tool::Erase => {
+                            self.board.clear_tile(x, y);
+                            self.redraw_board(owner);
+                        }
+                        Tool::Rotate => {
+                            if self.board.rotate_tile(x, y) {
+                                self.redraw_board(owner);
+                            }
+                        }
+                        _ => (),
+                    }
+                }
                 GlobalConstants::BUTTON_LEFT => {
-                    self.board.set_tile(tile_hover_u.x, tile_hover_u.y, self.tile_kind.tile_data());
-                    owner.set_cellv(tile_hover, self.tile_kind.tile_num(), false, false, false);
-                },
+                    self.board.end_group();
+                    self.last_drag = None;
+                }
+                GlobalConstants::BUTTON_RIGHT if event.is_pressed() => {
+                    // Clearing also cuts the neighbors' connections to the
+                    // tile, so they need redrawing too.
+                    self.board.clear_tile(tile_hover_u.x, tile_hover_u.y);
+                    self.redraw_board(owner);
+                }
                 _ => (),
             }
         }
+
+        if let Some(event) = event.clone().cast::<InputEventKey>() {
+            let event = unsafe { event.assume_safe() };
+            if event.is_pressed() && event.control() {
+                let changed = match event.scancode() {
+                    GlobalConstants::KEY_Z => self.board.undo(),
+                    GlobalConstants::KEY_Y => self.board.redo(),
+                    _ => false,
+                };
+                if changed {
+                    self.redraw_board(owner);
+                }
+            } else if event.is_pressed() && event.scancode() == GlobalConstants::KEY_R {
+                let rotated = self
+                    .cursor
+                    .and_then(|v| v.try_cast::<usize>())
+                    .map_or(false, |v| self.board.rotate_tile(v.x, v.y));
+                if rotated {
+                    self.redraw_board(owner);
+                }
+            }
+        }
+    }
+
+    #[export]
+    fn _change_tile(&mut self, _owner: &TileMap, button_pressed: bool, tile_ty: u8) {
+        // Releasing a button means another one got pressed.
+        if !button_pressed {
+            return;
+        }
+        if let Some(entry) = PALETTE.get(tile_ty as usize) {
+            self.tool = Tool::Place(entry);
+        }
     }
 
     #[export]
-    fn _change_tile(&mut self, owner: &TileMap, button_pressed: bool, tile_ty: u8) {
-        godot_print!("Changing tile: {:?}", tile_ty);
-        if tile_ty >= TileKind::MAX {
+    fn _change_tool(&mut self, _owner: &TileMap, button_pressed: bool, index: u8) {
+        if !button_pressed {
             return;
         }
-        self.tile_kind = TileKind(tile_ty)
+        if let Some(&(_, tool)) = EDIT_TOOLS.get(index as usize) {
+            self.tool = tool;
+        }
+    }
+
+    /// Builds a game from the board, unless there is one already.
+    fn start(&mut self, owner: &TileMap) {
+        if self.game.is_some() {
+            return;
+        }
+        // A drag in progress ends here, since edits stop.
+        self.board.end_group();
+        self.last_drag = None;
+        if let Some(last_hover) = self.last_hover.take() {
+            owner.set_cellv(last_hover, CLEAR, false, false, false);
+        }
+        self.game = Some(Game::with(self.board.clone()));
+        self.elapsed = 0.0;
+    }
+
+    #[export]
+    fn _play_pause(&mut self, owner: &TileMap) {
+        self.start(owner);
+        self.running = !self.running;
+        self.redraw_board(owner);
+    }
+
+    #[export]
+    fn _step(&mut self, owner: &TileMap) {
+        self.start(owner);
+        self.running = false;
+        if let Some(game) = &mut self.game {
+            game.step();
+        }
+        self.redraw_board(owner);
+        self.show_verdict(owner);
+    }
+
+    #[export]
+    fn _step_back(&mut self, owner: &TileMap) {
+        self.running = false;
+        if let Some(game) = &mut self.game {
+            game.step_back();
+        }
+        self.redraw_board(owner);
+        self.show_verdict(owner);
+    }
+
+    /// Throws the game away, going back to editing the board.
+    #[export]
+    fn _stop_reset(&mut self, owner: &TileMap) {
+        self.running = false;
+        self.game = None;
+        self.redraw_board(owner);
+        self.show_verdict(owner);
+    }
+
+    #[export]
+    fn _set_tick_rate(&mut self, _owner: &TileMap, tick_rate: f64) {
+        if tick_rate > 0.0 {
+            self.tick_rate = tick_rate;
+        }
     }
+
+    #[export]
+    fn _process(&mut self, owner: &TileMap, delta: f64) {
+        if !self.running {
+            return;
+        }
+        // Signals move between ticks too.
+        self.draw_signals(owner);
+        let game = match &mut self.game {
+            Some(game) => game,
+            None => return,
+        };
+        self.elapsed += delta;
+        let period = 1.0 / self.tick_rate;
+        let mut stepped = false;
+        while self.elapsed >= period {
+            self.elapsed -= period;
+            game.step();
+            stepped = true;
+            if game.outcome(MAX_TICKS).is_some() {
+                self.running = false;
+                self.elapsed = 0.0;
+                break;
+            }
+        }
+        if stepped {
+            self.redraw_board(owner);
+            self.show_verdict(owner);
+        }
+    }
+
+    /// Shows how the game ended below the board, or nothing while it goes
+    /// on.
+    fn show_verdict(&self, owner: &TileMap) {
+        let game = self.game.as_ref();
+        let text = match game.and_then(|game| game.outcome(MAX_TICKS)) {
+            Some(Verdict::Pass) => format!("Passed in {} ticks", game.map_or(0, Game::tick)),
+            Some(verdict) => format!("Failed at tick {}: {}", game.map_or(0, Game::tick), verdict),
+            None => String::new(),
+        };
+        let label = owner
+            .get_node("../GUI/Verdict")
+            .and_then(|label| unsafe { label.assume_safe() }.cast::<Label>());
+        if let Some(label) = label {
+            label.set_text(text);
+        }
+    }
+}
+
+/// A toolbar button in `group`, calling `method` on `owner` with `index` when
+/// toggled. It starts out `pressed` without calling `method`.
+fn tool_button(
+    owner: &TileMap,
+    group: &Ref<ButtonGroup, Shared>,
+    text: &str,
+    method: &str,
+    index: usize,
+    pressed: bool,
+) -> Ref<Button, Unique> {
+    let button = Button::new();
+    button.set_text(text);
+    button.set_toggle_mode(true);
+    button.set_button_group(group.clone());
+    button.set_pressed(pressed);
+    let binds = VariantArray::new();
+    binds.push(index as i64);
+    let target = unsafe { owner.assume_shared() };
+    if let Err(err) = button.connect("toggled", target, method, binds.into_shared(), 0) {
+        godot_print!("Couldn't connect the {} button: {:?}", text, err);
+    }
+    button
+}
+
+/// Sets a cell of the TileMap, or clears it.
+fn set_cell(owner: &TileMap, x: i64, y: i64, cell: Option<Cell>) {
+    let (id, flip_x, flip_y, transpose) = cell.unwrap_or((CLEAR, false, false, false));
+    owner.set_cell(x, y, id, flip_x, flip_y, transpose, Vector2::zero());
+}
+
+/// Whether `signal` leaves its tile on the next tick, instead of waiting on a
+/// slow wire or at a closed lock.
+fn will_move(board: &Board, signal: &Signal) -> bool {
+    let (x, y) = signal.position();
+    if signal.delay() > 0 || !board.is_connected(x, y, signal.direction()) {
+        return false;
+    }
+    let (x, y) = signal.direction().step(x, y);
+    !matches!(board.get_tile(x, y), Some(Tile::Lock { locked: true }))
 }
 
 fn adjascency(from: Vector2, to: Vector2) -> Option<Orientation> {
@@ -189,31 +572,6 @@ fn adjascency(from: Vector2, to: Vector2) -> Option<Orientation> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct TileKind(u8);
-
-impl TileKind {
-    const WIRE: TileKind = TileKind(0);
-    const INPUT: TileKind = TileKind(1);
-    const MAX: u8 = 2;
-
-    pub fn tile_num(&self) -> i64 {
-        match self {
-            &TileKind::WIRE => CABLE_UNCONNECTED,
-            &TileKind::INPUT => IO_UNCONNECTED,
-            _ => CLEAR,
-        }
-    }
-
-    pub fn tile_data(&self) -> Tile {
-        match self {
-            &TileKind::WIRE => Tile::Wire { slow: false },
-            &TileKind::INPUT => Tile::Input { data: vec![] },
-            _ => Tile::Empty,
-        }
-    }
-}
-
 fn init(handle: InitHandle) {
     std::env::set_var("RUST_BACKTRACE", "1");
     handle.add_class::<GridTileMap>();
@@ -0,0 +1,287 @@
+use super_prime_mover::{Board, BoardIcon, Orientation, Tile, TileKind};
+
+/// How big a new sub-board is.
+const SUB_BOARD_SIZE: usize = 5;
+
+/// What a new Input sends, so that a board can be run as soon as it's wired
+/// up. Outputs expect nothing, so the verdict tells what reached them.
+const DEFAULT_INPUT: [u32; 5] = [1, 2, 3, 4, 5];
+
+/// Which tileset tile draws a tile.
+pub enum Cells {
+    /// One tile per connection mask, see [`mask`]. `None` for connections
+    /// the tile can't have.
+    ByMask([Option<i64>; 16]),
+    /// One tile, drawn facing north, turned to face the tile's orientation.
+    Oriented(i64),
+    Single(i64),
+}
+
+/// A cell of the TileMap: the tile ID, and its flip_x, flip_y and transpose
+/// flags.
+pub type Cell = (i64, bool, bool, bool);
+
+impl Cells {
+    pub fn cell(&self, connections: [bool; 4], orientation: Orientation) -> Option<Cell> {
+        match *self {
+            Cells::ByMask(ref cells) => {
+                cells[mask(connections)].map(|id| (id, false, false, false))
+            }
+            Cells::Oriented(id) => {
+                // Transposing happens before flipping.
+                let (flip_x, flip_y, transpose) = match orientation {
+                    Orientation::North => (false, false, false),
+                    Orientation::East => (true, false, true),
+                    Orientation::South => (true, true, false),
+                    Orientation::West => (false, true, true),
+                };
+                Some((id, flip_x, flip_y, transpose))
+            }
+            Cells::Single(id) => Some((id, false, false, false)),
+        }
+    }
+
+    /// The cell of a freshly placed tile, to show where it would go.
+    pub fn preview(&self) -> Option<Cell> {
+        self.cell([false; 4], Orientation::North)
+    }
+}
+
+/// The connections of a tile as bits, north first.
+pub fn mask([n, e, s, w]: [bool; 4]) -> usize {
+    n as usize | (e as usize) << 1 | (s as usize) << 2 | (w as usize) << 3
+}
+
+/// A kind of tile the player can place, and how tiles of that kind look.
+pub struct PaletteEntry {
+    pub name: &'static str,
+    pub icon: &'static str,
+    pub cells: Cells,
+    /// The kind of the tile, and its flag (slow, broken, reversed or locked)
+    /// if it has one. Tells which entry draws a tile, see [`style`].
+    pub style: (TileKind, bool),
+    /// The tile placed, with its data stored in `board`. Placing the same
+    /// entry again reuses that data, so the store doesn't grow with every
+    /// click. Directional tiles start out facing north.
+    pub new_tile: fn(&mut Board) -> Tile,
+}
+
+#[rustfmt::skip]
+const CABLE: [Option<i64>; 16] = [
+    Some(9), Some(5), Some(1), Some(3), Some(8), Some(10), Some(6), None,
+    Some(0), Some(4), Some(2), None, Some(7), None, None, None,
+];
+#[rustfmt::skip]
+const IO: [Option<i64>; 16] = [
+    Some(15), Some(13), Some(11), None, Some(12), None, None, None,
+    Some(14), None, None, None, None, None, None, None,
+];
+
+/// Added to a tile ID, gives a tinted copy of the tile: for slow wires,
+/// outputs, broken unusable tiles, and reversed sorters and flipflops.
+const TINTED: i64 = 29;
+
+const fn tinted(cells: [Option<i64>; 16]) -> [Option<i64>; 16] {
+    let mut tinted = [None; 16];
+    let mut i = 0;
+    while i < 16 {
+        if let Some(id) = cells[i] {
+            tinted[i] = Some(id + TINTED);
+        }
+        i += 1;
+    }
+    tinted
+}
+
+pub static PALETTE: &[PaletteEntry] = &[
+    PaletteEntry {
+        name: "Cable",
+        icon: "res://assets/cable/unconnected_cable.png",
+        cells: Cells::ByMask(CABLE),
+        style: (TileKind::Wire, false),
+        new_tile: |_| Tile::Wire { slow: false },
+    },
+    PaletteEntry {
+        name: "Input",
+        icon: "res://assets/io/io_unconnected.png",
+        cells: Cells::ByMask(IO),
+        style: (TileKind::Input, false),
+        new_tile: |board| Tile::Input {
+            data: board.store_mut().share_data(&DEFAULT_INPUT),
+        },
+    },
+    PaletteEntry {
+        name: "Slow",
+        icon: "res://assets/cable/unconnected_cable.png",
+        cells: Cells::ByMask(tinted(CABLE)),
+        style: (TileKind::Wire, true),
+        new_tile: |_| Tile::Wire { slow: true },
+    },
+    PaletteEntry {
+        name: "Output",
+        icon: "res://assets/io/io_unconnected.png",
+        cells: Cells::ByMask(tinted(IO)),
+        style: (TileKind::Output, false),
+        new_tile: |board| Tile::Output {
+            expected_data: board.store_mut().share_data(&[]),
+        },
+    },
+    PaletteEntry {
+        name: "Unusable",
+        icon: "res://assets/tiles/unusable.png",
+        cells: Cells::Single(16),
+        style: (TileKind::Unusable, false),
+        new_tile: |_| Tile::Unusable { broken: false },
+    },
+    PaletteEntry {
+        name: "Broken",
+        icon: "res://assets/tiles/unusable.png",
+        cells: Cells::Single(16 + TINTED),
+        style: (TileKind::Unusable, true),
+        new_tile: |_| Tile::Unusable { broken: true },
+    },
+    PaletteEntry {
+        name: "Bridge",
+        icon: "res://assets/tiles/bridge.png",
+        cells: Cells::Single(17),
+        style: (TileKind::Bridge, false),
+        new_tile: |_| Tile::Bridge,
+    },
+    PaletteEntry {
+        name: "Joiner",
+        icon: "res://assets/tiles/joiner.png",
+        cells: Cells::Oriented(18),
+        style: (TileKind::Joiner, false),
+        new_tile: |_| Tile::Joiner {
+            orientation: Orientation::North,
+        },
+    },
+    PaletteEntry {
+        name: "Cloner",
+        icon: "res://assets/tiles/cloner.png",
+        cells: Cells::Single(19),
+        style: (TileKind::Cloner, false),
+        new_tile: |_| Tile::Cloner,
+    },
+    PaletteEntry {
+        name: "Sorter",
+        icon: "res://assets/compare/compare.png",
+        cells: Cells::Oriented(20),
+        style: (TileKind::Sorter, false),
+        new_tile: |_| Tile::Sorter {
+            orientation: Orientation::North,
+            reversed: false,
+        },
+    },
+    PaletteEntry {
+        name: "Sorter R",
+        icon: "res://assets/compare/compare.png",
+        cells: Cells::Oriented(20 + TINTED),
+        style: (TileKind::Sorter, true),
+        new_tile: |_| Tile::Sorter {
+            orientation: Orientation::North,
+            reversed: true,
+        },
+    },
+    PaletteEntry {
+        name: "Deleter",
+        icon: "res://assets/tiles/deleter.png",
+        cells: Cells::Single(21),
+        style: (TileKind::Deleter, false),
+        new_tile: |_| Tile::Deleter,
+    },
+    PaletteEntry {
+        name: "Flipflop",
+        icon: "res://assets/tiles/flipflop.png",
+        cells: Cells::Oriented(22),
+        style: (TileKind::Flipflop, false),
+        new_tile: |_| Tile::Flipflop {
+            orientation: Orientation::North,
+            reversed: false,
+        },
+    },
+    PaletteEntry {
+        name: "Flipflop R",
+        icon: "res://assets/tiles/flipflop.png",
+        cells: Cells::Oriented(22 + TINTED),
+        style: (TileKind::Flipflop, true),
+        new_tile: |_| Tile::Flipflop {
+            orientation: Orientation::North,
+            reversed: true,
+        },
+    },
+    PaletteEntry {
+        name: "+1",
+        icon: "res://assets/tiles/incrementer.png",
+        cells: Cells::Single(23),
+        style: (TileKind::Incrementer, false),
+        new_tile: |_| Tile::Incrementer { reversed: false },
+    },
+    PaletteEntry {
+        name: "-1",
+        icon: "res://assets/tiles/decrementer.png",
+        cells: Cells::Single(24),
+        style: (TileKind::Incrementer, true),
+        new_tile: |_| Tile::Incrementer { reversed: true },
+    },
+    PaletteEntry {
+        name: "Button",
+        icon: "res://assets/tiles/button.png",
+        cells: Cells::Oriented(25),
+        style: (TileKind::Button, false),
+        new_tile: |_| Tile::Button {
+            orientation: Orientation::North,
+        },
+    },
+    PaletteEntry {
+        name: "Lock",
+        icon: "res://assets/tiles/lock.png",
+        cells: Cells::Single(26),
+        style: (TileKind::Lock, true),
+        new_tile: |_| Tile::Lock { locked: true },
+    },
+    PaletteEntry {
+        name: "Open lock",
+        icon: "res://assets/tiles/lock_open.png",
+        cells: Cells::Single(27),
+        style: (TileKind::Lock, false),
+        new_tile: |_| Tile::Lock { locked: false },
+    },
+    PaletteEntry {
+        name: "Board",
+        icon: "res://assets/tiles/sub_board.png",
+        cells: Cells::Single(28),
+        style: (TileKind::SubBoard, false),
+        new_tile: |board| {
+            let contents = Board::new(SUB_BOARD_SIZE, SUB_BOARD_SIZE);
+            Tile::SubBoard {
+                contents: board
+                    .store_mut()
+                    .share_sub_board(contents, BoardIcon::Green),
+            }
+        },
+    },
+];
+
+/// What tells tiles drawn differently apart, see [`PaletteEntry::style`].
+pub fn style(tile: &Tile) -> (TileKind, bool) {
+    match *tile {
+        Tile::Wire { slow: flag }
+        | Tile::Unusable { broken: flag }
+        | Tile::Sorter { reversed: flag, .. }
+        | Tile::Flipflop { reversed: flag, .. }
+        | Tile::Incrementer { reversed: flag }
+        | Tile::Lock { locked: flag } => (tile.kind(), flag),
+        _ => (tile.kind(), false),
+    }
+}
+
+/// The entry drawing `tile`. Empty tiles have none.
+pub fn entry_for(tile: &Tile) -> Option<&'static PaletteEntry> {
+    let style = style(tile);
+    PALETTE.iter().find(|entry| entry.style == style)
+}
+
+pub fn orientation(tile: &Tile) -> Orientation {
+    tile.orientation().unwrap_or(Orientation::North)
+}
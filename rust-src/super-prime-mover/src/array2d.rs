@@ -1,4 +1,4 @@
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Array2D<T> {
     inner: Box<[T]>,
     width: usize
@@ -13,7 +13,7 @@ impl<T> Array2D<T> {
         vec.resize_with(height * width, Default::default);
         Array2D {
             inner: vec.into_boxed_slice(),
-            width: width,
+            width,
         }
     }
     #[inline(always)]
@@ -27,6 +27,10 @@ impl<T> Array2D<T> {
         self.inner.get_mut(y.checked_mul(self.width)?.checked_add(x)?)
     }
 
+    pub fn iter(&self) -> IterArray2D<'_, T> {
+        self.into_iter()
+    }
+
     pub fn get_mut2(&mut self, x1: usize, y1: usize, x2: usize, y2: usize) -> (Option<&mut T>, Option<&mut T>) {
         let slice = &mut self.inner[..];
 
@@ -96,7 +100,7 @@ impl<'a, T> IntoIterator for &'a mut Array2D<T> {
     type IntoIter = IterMutArray2D<'a, T>;
 
     fn into_iter(self) -> Self::IntoIter {
-        IterMutArray2D{ iter: (&mut self.inner).iter_mut().enumerate(), width: self.width }
+        IterMutArray2D{ iter: self.inner.iter_mut().enumerate(), width: self.width }
     }
 }
 
@@ -0,0 +1,1262 @@
+use crate::array2d::Array2D;
+use crate::trace::{Event, EventSink, SubBoardSink, Turn};
+use crate::{Board, Orientation, Reaction, Tile, TileKind};
+use std::fmt;
+
+/// A value travelling across the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signal {
+    value: u32,
+    x: usize,
+    y: usize,
+    /// The side through which the signal leaves its tile.
+    direction: Orientation,
+    /// The number of ticks left before the signal leaves its tile.
+    delay: u32,
+}
+
+impl Signal {
+    pub fn new(x: usize, y: usize, direction: Orientation, value: u32) -> Signal {
+        Signal {
+            value,
+            x,
+            y,
+            direction,
+            delay: 0,
+        }
+    }
+
+    /// Makes the signal wait `delay` extra ticks before leaving its tile.
+    pub fn delayed(self, delay: u32) -> Signal {
+        Signal { delay, ..self }
+    }
+
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+
+    pub fn position(&self) -> (usize, usize) {
+        (self.x, self.y)
+    }
+
+    pub fn direction(&self) -> Orientation {
+        self.direction
+    }
+
+    pub fn delay(&self) -> u32 {
+        self.delay
+    }
+}
+
+/// The outcome of [`Game::run_to_completion`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// Every output received exactly the values it expected.
+    Pass,
+    /// The output at `output` received `actual` where `expected` was due.
+    WrongValue {
+        output: (usize, usize),
+        index: usize,
+        expected: u32,
+        actual: u32,
+    },
+    /// The output at `output` only received `actual` of its `expected`
+    /// values.
+    MissingOutputs {
+        output: (usize, usize),
+        expected: usize,
+        actual: usize,
+    },
+    /// The output at `output` received `actual` values, more than the
+    /// `expected` ones.
+    ExtraOutputs {
+        output: (usize, usize),
+        expected: usize,
+        actual: usize,
+    },
+    /// The board was still busy after `tick` ticks.
+    TimedOut { tick: u64 },
+    /// At `tick`, the board came back to the state it was in `period` ticks
+    /// before: it would loop forever.
+    Livelock { tick: u64, period: u64 },
+    /// At `tick`, every signal left was waiting at a locked lock, with no
+    /// way to get it unlocked. `locks` are the positions of those locks, or
+    /// of the sub-boards they are in.
+    Deadlock {
+        tick: u64,
+        locks: Vec<(usize, usize)>,
+    },
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Verdict::Pass => write!(f, "pass"),
+            Verdict::WrongValue {
+                output: (x, y),
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "output ({}, {}) received {} as value {}, expected {}",
+                x, y, actual, index, expected
+            ),
+            Verdict::MissingOutputs {
+                output: (x, y),
+                expected,
+                actual,
+            } => write!(
+                f,
+                "output ({}, {}) received {} of {} values",
+                x, y, actual, expected
+            ),
+            Verdict::ExtraOutputs {
+                output: (x, y),
+                expected,
+                actual,
+            } => write!(
+                f,
+                "output ({}, {}) received {} values, expected {}",
+                x, y, actual, expected
+            ),
+            Verdict::TimedOut { tick } => write!(f, "timed out after {} ticks", tick),
+            Verdict::Livelock { tick, period } => {
+                write!(f, "livelock at tick {}, period {}", tick, period)
+            }
+            Verdict::Deadlock { tick, locks } => {
+                write!(f, "deadlock at tick {}, signals stuck at", tick)?;
+                for (i, (x, y)) in locks.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { "," };
+                    write!(f, "{} ({}, {})", separator, x, y)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Tracks how far an Input tile got through its data.
+#[derive(Debug, Clone)]
+struct Feed {
+    x: usize,
+    y: usize,
+    sent: usize,
+}
+
+/// The values an Output tile received so far.
+#[derive(Debug, Clone)]
+struct Received {
+    x: usize,
+    y: usize,
+    values: Vec<u32>,
+}
+
+/// Everything the rest of a game depends on. When a game gets back to a
+/// state it was in before, it loops.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct State {
+    signals: Vec<Signal>,
+    tiles: Array2D<Tile>,
+    sent: Vec<usize>,
+    nested: Vec<State>,
+}
+
+/// A copy of a game at some tick, for [`Game::seek`] to go back to.
+#[derive(Debug, Clone)]
+struct Keyframe {
+    tick: u64,
+    state: State,
+    received: Vec<Vec<u32>>,
+    checkpoint: Option<(State, u64)>,
+    stall: Option<Verdict>,
+}
+
+/// How many ticks apart keyframes are saved. Seeking back replays at most
+/// this many ticks.
+const KEYFRAME_INTERVAL: u64 = 64;
+
+/// A sub-board tile, simulated alongside the board it sits on.
+struct Nested {
+    x: usize,
+    y: usize,
+    game: Game,
+}
+
+pub struct Game {
+    board: Board,
+    signals: Vec<Signal>,
+    tick: u64,
+    feeds: Vec<Feed>,
+    received: Vec<Received>,
+    nested: Vec<Nested>,
+    /// When simulating a sub-board, the Input/Output tiles wired to each of
+    /// its sides.
+    ports: Vec<(Orientation, usize, usize)>,
+    /// Signals that reached an Output port of a sub-board during the last
+    /// tick, along with the side they leave through.
+    exits: Vec<(Orientation, u32)>,
+    /// How many signals got blocked by a lock during the last tick.
+    blocked: usize,
+    /// A state saved at a power of two tick, along with that tick, that
+    /// later states get compared with to find loops (Brent's algorithm).
+    checkpoint: Option<(State, u64)>,
+    /// Why the game will never finish, once found out.
+    stall: Option<Verdict>,
+    /// Copies of the game, by tick, to seek back to.
+    keyframes: Vec<Keyframe>,
+}
+
+impl Game {
+    pub fn with(board: Board) -> Game {
+        let mut game = Game::nested(board);
+        for (x, y, tile) in &game.board.tiles {
+            match tile {
+                Tile::Input { .. } => game.feeds.push(Feed { x, y, sent: 0 }),
+                Tile::Output { .. } => game.received.push(Received {
+                    x,
+                    y,
+                    values: Vec::new(),
+                }),
+                _ => (),
+            }
+        }
+        game.ports.clear();
+        game.keyframes.push(game.keyframe());
+        game
+    }
+
+    /// Simulates `board` as the contents of a sub-board: its Input and Output
+    /// tiles only act as ports.
+    fn nested(board: Board) -> Game {
+        let mut nested = Vec::new();
+        for (x, y, tile) in &board.tiles {
+            let sub_board = match tile {
+                Tile::SubBoard { contents } => board.store().sub_board(*contents),
+                _ => None,
+            };
+            if let Some(sub_board) = sub_board {
+                nested.push(Nested {
+                    x,
+                    y,
+                    game: Game::nested(sub_board.contents.clone()),
+                });
+            }
+        }
+        let ports = Orientation::BY_PRIORITY
+            .iter()
+            .filter_map(|&side| board.port(side).map(|(x, y)| (side, x, y)))
+            .collect();
+
+        Game {
+            board,
+            signals: Vec::new(),
+            tick: 0,
+            feeds: Vec::new(),
+            received: Vec::new(),
+            nested,
+            ports,
+            exits: Vec::new(),
+            blocked: 0,
+            checkpoint: None,
+            stall: None,
+            keyframes: Vec::new(),
+        }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn signals(&self) -> &[Signal] {
+        &self.signals
+    }
+
+    /// The number of ticks simulated so far.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// The values the Output tile at `x`, `y` received so far.
+    pub fn received(&self, x: usize, y: usize) -> Option<&[u32]> {
+        self.received
+            .iter()
+            .find(|received| (received.x, received.y) == (x, y))
+            .map(|received| &received.values[..])
+    }
+
+    /// Whether no signal is left on the board, sub-boards included.
+    pub fn is_idle(&self) -> bool {
+        self.signals.is_empty() && self.nested.iter().all(|nested| nested.game.is_idle())
+    }
+
+    /// Whether every input was sent and no signal is left on the board.
+    pub fn is_finished(&self) -> bool {
+        self.is_idle()
+            && self
+                .feeds
+                .iter()
+                .all(|feed| self.feed_data(feed).len() <= feed.sent)
+    }
+
+    /// How the game ended, if it did: what the outputs received once it is
+    /// finished, or why it never will, or [`Verdict::TimedOut`] once it ran
+    /// for `limit` ticks.
+    pub fn outcome(&self, limit: u64) -> Option<Verdict> {
+        if self.is_finished() {
+            Some(self.verdict())
+        } else if let Some(stall) = &self.stall {
+            Some(stall.clone())
+        } else if self.tick >= limit {
+            Some(Verdict::TimedOut { tick: self.tick })
+        } else {
+            None
+        }
+    }
+
+    /// Steps the board until every input went through it, then checks what
+    /// the outputs received. Gives up after `limit` ticks, or as soon as the
+    /// board livelocks or deadlocks.
+    pub fn run_to_completion(&mut self, limit: u64) -> Verdict {
+        loop {
+            if let Some(verdict) = self.outcome(limit) {
+                return verdict;
+            }
+            self.step();
+        }
+    }
+
+    /// Compares what every output received so far with what it expects.
+    pub fn verdict(&self) -> Verdict {
+        for received in &self.received {
+            let expected = match self.board.get_tile(received.x, received.y) {
+                Some(Tile::Output { expected_data }) => {
+                    self.board.store().data(*expected_data).unwrap_or(&[])
+                }
+                _ => continue,
+            };
+            let output = (received.x, received.y);
+            let mismatch = expected
+                .iter()
+                .zip(&received.values)
+                .position(|(expected, actual)| expected != actual);
+            if let Some(index) = mismatch {
+                return Verdict::WrongValue {
+                    output,
+                    index,
+                    expected: expected[index],
+                    actual: received.values[index],
+                };
+            }
+            if received.values.len() < expected.len() {
+                return Verdict::MissingOutputs {
+                    output,
+                    expected: expected.len(),
+                    actual: received.values.len(),
+                };
+            }
+            if received.values.len() > expected.len() {
+                return Verdict::ExtraOutputs {
+                    output,
+                    expected: expected.len(),
+                    actual: received.values.len(),
+                };
+            }
+        }
+        Verdict::Pass
+    }
+
+    fn feed_data(&self, feed: &Feed) -> &[u32] {
+        match self.board.get_tile(feed.x, feed.y) {
+            Some(Tile::Input { data }) => self.board.store().data(*data).unwrap_or(&[]),
+            _ => &[],
+        }
+    }
+
+    /// Once the board is empty, every input sends its next value through its
+    /// connection.
+    fn release_inputs(&mut self) {
+        for i in 0..self.feeds.len() {
+            let feed = &self.feeds[i];
+            let (x, y) = (feed.x, feed.y);
+            let value = match self.feed_data(feed).get(feed.sent) {
+                Some(&value) => value,
+                None => continue,
+            };
+            self.feeds[i].sent += 1;
+            let side = Orientation::BY_PRIORITY
+                .iter()
+                .copied()
+                .find(|&side| self.board.is_connected(x, y, side));
+            if let Some(side) = side {
+                self.signals.push(Signal::new(x, y, side, value));
+            }
+        }
+    }
+
+    /// Puts a new signal on the board. It will start moving on the next call
+    /// to [`Game::step`].
+    ///
+    /// The signal becomes part of the game's past: seeking back before it
+    /// then forward again brings it back.
+    pub fn spawn(&mut self, signal: Signal) {
+        self.signals.push(signal);
+        self.checkpoint = None;
+        self.stall = None;
+        let tick = self.tick;
+        self.keyframes.retain(|keyframe| keyframe.tick < tick);
+        self.keyframes.push(self.keyframe());
+    }
+
+    fn keyframe(&self) -> Keyframe {
+        Keyframe {
+            tick: self.tick,
+            state: self.state(),
+            received: self.received.iter().map(|r| r.values.clone()).collect(),
+            checkpoint: self.checkpoint.clone(),
+            stall: self.stall.clone(),
+        }
+    }
+
+    fn restore(&mut self, keyframe: &Keyframe) {
+        self.restore_state(&keyframe.state, keyframe.tick);
+        for (received, values) in self.received.iter_mut().zip(&keyframe.received) {
+            received.values.clone_from(values);
+        }
+        self.checkpoint = keyframe.checkpoint.clone();
+        self.stall = keyframe.stall.clone();
+    }
+
+    fn restore_state(&mut self, state: &State, tick: u64) {
+        self.signals.clone_from(&state.signals);
+        self.board.tiles.clone_from(&state.tiles);
+        for (feed, &sent) in self.feeds.iter_mut().zip(&state.sent) {
+            feed.sent = sent;
+        }
+        for (nested, state) in self.nested.iter_mut().zip(&state.nested) {
+            nested.game.restore_state(state, tick);
+        }
+        self.tick = tick;
+        self.blocked = 0;
+    }
+
+    fn keyframe_at(&self, tick: u64) -> Result<usize, usize> {
+        self.keyframes
+            .binary_search_by_key(&tick, |keyframe| keyframe.tick)
+    }
+
+    /// Takes the game to the state it was, or will be, in at `tick`.
+    ///
+    /// Going back restores the latest keyframe before `tick`, then replays
+    /// the ticks in between, which gives the same states as the first time
+    /// since the simulation is deterministic.
+    pub fn seek(&mut self, tick: u64) {
+        if tick < self.tick {
+            let index = match self.keyframe_at(tick) {
+                Ok(index) => index,
+                // There is always a keyframe at tick 0.
+                Err(index) => index - 1,
+            };
+            let keyframes = std::mem::take(&mut self.keyframes);
+            self.restore(&keyframes[index]);
+            self.keyframes = keyframes;
+        }
+        while self.tick < tick {
+            self.step();
+        }
+    }
+
+    /// Takes the game back by one tick. Returns false if it was at tick 0
+    /// already.
+    pub fn step_back(&mut self) -> bool {
+        if self.tick == 0 {
+            return false;
+        }
+        self.seek(self.tick - 1);
+        true
+    }
+
+    fn state(&self) -> State {
+        State {
+            signals: self.signals.clone(),
+            tiles: self.board.tiles.clone(),
+            sent: self.feeds.iter().map(|feed| feed.sent).collect(),
+            nested: self
+                .nested
+                .iter()
+                .map(|nested| nested.game.state())
+                .collect(),
+        }
+    }
+
+    /// Whether the game is in `state`, without copying the current one.
+    fn is_in(&self, state: &State) -> bool {
+        self.signals == state.signals
+            && self.board.tiles == state.tiles
+            && self
+                .feeds
+                .iter()
+                .map(|feed| feed.sent)
+                .eq(state.sent.iter().copied())
+            && self
+                .nested
+                .iter()
+                .zip(&state.nested)
+                .all(|(nested, state)| nested.game.is_in(state))
+    }
+
+    /// Whether every signal, sub-boards included, got blocked during the last
+    /// tick.
+    fn is_blocked(&self) -> bool {
+        self.blocked == self.signals.len()
+            && self.nested.iter().all(|nested| nested.game.is_blocked())
+    }
+
+    /// Looks for the game looping or being deadlocked, after a tick.
+    fn detect_stall(&mut self) {
+        if self.stall.is_some() {
+            return;
+        }
+        if !self.is_idle() && self.is_blocked() {
+            let mut locks: Vec<_> = self
+                .signals
+                .iter()
+                .map(|signal| signal.direction.step(signal.x, signal.y))
+                .chain(
+                    self.nested
+                        .iter()
+                        .filter(|nested| !nested.game.is_idle())
+                        .map(|nested| (nested.x, nested.y)),
+                )
+                .collect();
+            locks.sort_by_key(|&(x, y)| (y, x));
+            locks.dedup();
+            self.stall = Some(Verdict::Deadlock {
+                tick: self.tick,
+                locks,
+            });
+            return;
+        }
+
+        if let Some((state, tick)) = &self.checkpoint {
+            if self.is_in(state) {
+                self.stall = Some(Verdict::Livelock {
+                    tick: self.tick,
+                    period: self.tick - tick,
+                });
+                return;
+            }
+        }
+        if self.tick.is_power_of_two() {
+            self.checkpoint = Some((self.state(), self.tick));
+        }
+    }
+
+    /// Advances the simulation by one tick.
+    ///
+    /// Every signal travels along the connection on its `direction` side and
+    /// is handed to the tile it enters, which decides where it goes next
+    /// through [`Tile::react`]. Signals whose side isn't connected fall off
+    /// the board. Signals emitted by a tile with a [`Tile::delay`] wait that
+    /// many ticks before moving on.
+    ///
+    /// Signals are settled tile by tile, in row-major order. Signals entering
+    /// the same tile on the same tick go in [`Orientation::priority`] order
+    /// of the side they enter from.
+    ///
+    /// Whenever the board is empty, the inputs first send their next value.
+    ///
+    /// Sub-boards are stepped along with the board they sit on. Signals
+    /// entering a sub-board are sent by its Input port on that side, and
+    /// start moving inside on the next tick. Signals reaching one of its
+    /// Output ports leave the sub-board tile through that side on the next
+    /// tick.
+    ///
+    /// Afterwards, the game checks whether it got back to an earlier state,
+    /// or got all its signals stuck at locks, see [`Game::outcome`].
+    ///
+    /// Ticks that were already simulated once are restored from their
+    /// keyframe, if they have one, see [`Game::seek`].
+    pub fn step(&mut self) {
+        self.step_traced(&mut ());
+    }
+
+    /// Steps the game like [`Game::step`], telling `sink` what happens on
+    /// the board, and inside its sub-boards, along the way.
+    pub fn step_traced(&mut self, sink: &mut dyn EventSink) {
+        self.advance(sink);
+        if let Ok(index) = self.keyframe_at(self.tick) {
+            // Brings back the signals spawned at that tick.
+            let keyframes = std::mem::take(&mut self.keyframes);
+            self.restore(&keyframes[index]);
+            self.keyframes = keyframes;
+            return;
+        }
+        self.detect_stall();
+        let last = self.keyframes.last().map_or(0, |keyframe| keyframe.tick);
+        if self.tick % KEYFRAME_INTERVAL == 0 && self.tick > last {
+            self.keyframes.push(self.keyframe());
+        }
+    }
+
+    /// Steps the board, and its sub-boards, by one tick.
+    fn advance(&mut self, sink: &mut dyn EventSink) {
+        let tick = self.tick + 1;
+        self.blocked = 0;
+        if self.is_idle() {
+            let released = self.signals.len();
+            self.release_inputs();
+            for signal in &self.signals[released..] {
+                sink.record(Event::Spawned {
+                    tick,
+                    at: signal.position(),
+                    value: signal.value,
+                });
+            }
+        }
+
+        // Signals leaving a sub-board only move on from its tile on the next
+        // tick, like those emitted by any other tile.
+        let mut exits = Vec::new();
+        for nested in &mut self.nested {
+            nested.game.advance(&mut SubBoardSink {
+                sink: &mut *sink,
+                at: (nested.x, nested.y),
+            });
+            for (side, value) in nested.game.exits.drain(..) {
+                sink.record(Event::Spawned {
+                    tick,
+                    at: (nested.x, nested.y),
+                    value,
+                });
+                exits.push(Signal::new(nested.x, nested.y, side, value));
+            }
+        }
+
+        let mut signals = std::mem::take(&mut self.signals);
+        signals.sort_by_key(|signal| {
+            let (x, y) = signal.direction.step(signal.x, signal.y);
+            (y, x, signal.direction.opposite().priority())
+        });
+        for mut signal in signals {
+            if signal.delay > 0 {
+                signal.delay -= 1;
+                self.signals.push(signal);
+                continue;
+            }
+            if !self
+                .board
+                .is_connected(signal.x, signal.y, signal.direction)
+            {
+                sink.record(Event::Deleted {
+                    tick,
+                    at: signal.position(),
+                    value: signal.value,
+                });
+                continue;
+            }
+            let (x, y) = signal.direction.step(signal.x, signal.y);
+            let connections = self.board.get_connections(x, y);
+            let (reaction, delay, kind) = match self.board.get_tile_mut(x, y) {
+                Some(tile) => (
+                    tile.react(signal.direction.opposite(), signal.value, connections),
+                    tile.delay(),
+                    tile.kind(),
+                ),
+                None => (Reaction::Consume, 0, TileKind::Empty),
+            };
+            let emit = |side, value| Signal::new(x, y, side, value).delayed(delay);
+            if reaction != Reaction::Block {
+                sink.record(Event::Moved {
+                    tick,
+                    from: signal.position(),
+                    to: (x, y),
+                    value: signal.value,
+                });
+            }
+
+            match reaction {
+                Reaction::Emit(side, value) => {
+                    match kind {
+                        TileKind::Sorter => sink.record(Event::Sorted {
+                            tick,
+                            at: (x, y),
+                            value,
+                            turn: Turn::between(signal.direction, side),
+                        }),
+                        TileKind::Flipflop | TileKind::Lock | TileKind::Incrementer => {
+                            sink.record(Event::Activated {
+                                tick,
+                                at: (x, y),
+                                tile: kind,
+                            })
+                        }
+                        _ => (),
+                    }
+                    self.signals.push(emit(side, value));
+                }
+                Reaction::Split(sides, value) => {
+                    sink.record(Event::Cloned {
+                        tick,
+                        at: (x, y),
+                        value,
+                    });
+                    for &side in &sides {
+                        self.signals.push(emit(side, value));
+                    }
+                }
+                Reaction::Press(side, value) => {
+                    sink.record(Event::Activated {
+                        tick,
+                        at: (x, y),
+                        tile: kind,
+                    });
+                    self.unlock_all();
+                    self.signals.push(emit(side, value));
+                }
+                Reaction::Output(value) => {
+                    if let Some(received) = self
+                        .received
+                        .iter_mut()
+                        .find(|received| (received.x, received.y) == (x, y))
+                    {
+                        let expected = match self.board.get_tile(x, y) {
+                            Some(Tile::Output { expected_data }) => self
+                                .board
+                                .store()
+                                .data(*expected_data)
+                                .and_then(|data| data.get(received.values.len()))
+                                .copied(),
+                            _ => None,
+                        };
+                        sink.record(if expected == Some(value) {
+                            Event::OutputMatched {
+                                tick,
+                                at: (x, y),
+                                value,
+                            }
+                        } else {
+                            Event::OutputMismatched {
+                                tick,
+                                at: (x, y),
+                                value,
+                                expected,
+                            }
+                        });
+                        received.values.push(value);
+                    } else if let Some(&(side, ..)) =
+                        self.ports.iter().find(|port| (port.1, port.2) == (x, y))
+                    {
+                        self.exits.push((side, value));
+                    } else {
+                        // An Output inside a sub-board, away from its ports.
+                        sink.record(Event::Deleted {
+                            tick,
+                            at: (x, y),
+                            value,
+                        });
+                    }
+                }
+                Reaction::Enter(side, value) => {
+                    let entered = self
+                        .nested
+                        .iter_mut()
+                        .find(|nested| (nested.x, nested.y) == (x, y))
+                        .map_or(false, |nested| nested.game.enter_port(side, value));
+                    if !entered {
+                        sink.record(Event::Deleted {
+                            tick,
+                            at: (x, y),
+                            value,
+                        });
+                    }
+                }
+                Reaction::Consume => sink.record(Event::Deleted {
+                    tick,
+                    at: (x, y),
+                    value: signal.value,
+                }),
+                Reaction::Block => {
+                    self.blocked += 1;
+                    self.signals.push(signal);
+                }
+            }
+        }
+        self.signals.extend(exits);
+        self.tick += 1;
+    }
+
+    /// Sends `value` from the Input port on the given side of a sub-board.
+    /// Returns false if there is no wired up Input port on that side to take
+    /// it.
+    fn enter_port(&mut self, side: Orientation, value: u32) -> bool {
+        let port = self.ports.iter().find(|port| port.0 == side);
+        let (x, y) = match port {
+            Some(&(_, x, y)) => (x, y),
+            None => return false,
+        };
+        if let Some(Tile::Input { .. }) = self.board.get_tile(x, y) {
+            let direction = Orientation::BY_PRIORITY
+                .iter()
+                .copied()
+                .find(|&direction| self.board.is_connected(x, y, direction));
+            if let Some(direction) = direction {
+                self.signals.push(Signal::new(x, y, direction, value));
+                return true;
+            }
+        }
+        false
+    }
+
+    fn unlock_all(&mut self) {
+        for (_, _, tile) in &mut self.board.tiles {
+            if let Tile::Lock { locked } = tile {
+                *locked = false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{BoardIcon, SLOW_WIRE_DELAY};
+
+    #[test]
+    fn signal_follows_wire() {
+        let mut board = Board::default();
+        for x in 0..3 {
+            board.set_tile(x, 0, Tile::Wire { slow: false });
+        }
+        board.set_tile(2, 1, Tile::Wire { slow: false });
+        board.connect(0, 0, Orientation::East);
+        board.connect(1, 0, Orientation::East);
+        board.connect(2, 0, Orientation::South);
+
+        let mut game = Game::with(board);
+        game.spawn(Signal::new(0, 0, Orientation::East, 7));
+
+        game.step();
+        assert_eq!(game.signals(), &[Signal::new(1, 0, Orientation::East, 7)]);
+        game.step();
+        assert_eq!(game.signals(), &[Signal::new(2, 0, Orientation::South, 7)]);
+        game.step();
+        // (2, 1) is a dead end, the signal has nowhere to go.
+        assert_eq!(game.signals(), &[]);
+        assert_eq!(game.tick(), 3);
+    }
+
+    #[test]
+    fn slow_wire_holds_signal() {
+        let mut board = Board::default();
+        board.set_tile(0, 0, Tile::Wire { slow: false });
+        board.set_tile(1, 0, Tile::Wire { slow: true });
+        board.set_tile(2, 0, Tile::Wire { slow: false });
+        board.set_tile(2, 1, Tile::Wire { slow: false });
+        board.connect(0, 0, Orientation::East);
+        board.connect(1, 0, Orientation::East);
+        board.connect(2, 0, Orientation::South);
+
+        let mut game = Game::with(board);
+        game.spawn(Signal::new(0, 0, Orientation::East, 1));
+        game.step();
+        assert_eq!(game.signals()[0].position(), (1, 0));
+        for _ in 0..SLOW_WIRE_DELAY {
+            game.step();
+            assert_eq!(game.signals()[0].position(), (1, 0));
+        }
+        game.step();
+        assert_eq!(game.signals()[0].position(), (2, 0));
+        assert_eq!(game.tick(), 2 + u64::from(SLOW_WIRE_DELAY));
+    }
+
+    /// A joiner at (1, 1) fed from the north, west and south at once.
+    fn joiner_board() -> Board {
+        let mut board = Board::default();
+        for &(x, y) in &[(1, 0), (0, 1), (1, 2), (2, 1)] {
+            board.set_tile(x, y, Tile::Wire { slow: false });
+        }
+        board.set_tile(
+            1,
+            1,
+            Tile::Joiner {
+                orientation: Orientation::East,
+            },
+        );
+        board.connect(1, 1, Orientation::North);
+        board.connect(1, 1, Orientation::West);
+        board.connect(1, 1, Orientation::South);
+        board.connect(1, 1, Orientation::East);
+        board
+    }
+
+    #[test]
+    fn simultaneous_arrivals_follow_priority() {
+        let spawns = [
+            Signal::new(1, 0, Orientation::South, 1),
+            Signal::new(0, 1, Orientation::East, 2),
+            Signal::new(1, 2, Orientation::North, 3),
+        ];
+        let expected = [
+            Signal::new(1, 1, Orientation::East, 3),
+            Signal::new(1, 1, Orientation::East, 2),
+            Signal::new(1, 1, Orientation::East, 1),
+        ];
+
+        // Whatever order the signals were spawned in, the one coming from the
+        // south goes first, then west, then north.
+        for order in &[[0, 1, 2], [2, 1, 0], [1, 0, 2], [2, 0, 1]] {
+            let mut game = Game::with(joiner_board());
+            for &i in order {
+                game.spawn(spawns[i]);
+            }
+            game.step();
+            assert_eq!(game.signals(), &expected);
+        }
+    }
+
+    #[test]
+    fn cloner_emits_in_priority_order() {
+        let mut board = Board::default();
+        board.set_tile(0, 1, Tile::Wire { slow: false });
+        board.set_tile(1, 1, Tile::Cloner);
+        board.set_tile(1, 0, Tile::Wire { slow: false });
+        board.set_tile(1, 2, Tile::Wire { slow: false });
+        board.connect(1, 1, Orientation::North);
+        board.connect(1, 1, Orientation::West);
+        board.connect(1, 1, Orientation::South);
+
+        let mut game = Game::with(board);
+        game.spawn(Signal::new(0, 1, Orientation::East, 4));
+        game.step();
+        assert_eq!(
+            game.signals(),
+            &[
+                Signal::new(1, 1, Orientation::South, 4),
+                Signal::new(1, 1, Orientation::North, 4),
+            ]
+        );
+    }
+
+    /// Input at (0, 0) feeding a sorter at (2, 0). Zeros are deleted at
+    /// (2, 1), everything else reaches the output at (4, 0).
+    fn filter_board(data: Vec<u32>, expected_data: Vec<u32>) -> Board {
+        let mut board = Board::default();
+        let data = board.store_mut().add_data(data);
+        let expected_data = board.store_mut().add_data(expected_data);
+        board.set_tile(0, 0, Tile::Input { data });
+        board.set_tile(1, 0, Tile::Wire { slow: false });
+        board.set_tile(
+            2,
+            0,
+            Tile::Sorter {
+                orientation: Orientation::West,
+                reversed: false,
+            },
+        );
+        board.set_tile(2, 1, Tile::Deleter);
+        board.set_tile(3, 0, Tile::Wire { slow: false });
+        board.set_tile(4, 0, Tile::Output { expected_data });
+        board.connect(0, 0, Orientation::East);
+        board.connect(1, 0, Orientation::East);
+        board.connect(2, 0, Orientation::South);
+        board.connect(2, 0, Orientation::East);
+        board.connect(3, 0, Orientation::East);
+        board
+    }
+
+    #[test]
+    fn run_passes() {
+        let mut game = Game::with(filter_board(vec![0, 3, 0, 5], vec![3, 5]));
+        assert_eq!(game.run_to_completion(100), Verdict::Pass);
+        assert_eq!(game.received(4, 0), Some(&[3, 5][..]));
+        // One value at a time: 4 ticks for each value reaching the output, 3
+        // for each zero reaching the deleter.
+        assert_eq!(game.tick(), 14);
+    }
+
+    #[test]
+    fn run_wrong_value() {
+        let mut game = Game::with(filter_board(vec![0, 3, 0, 5], vec![3, 4]));
+        assert_eq!(
+            game.run_to_completion(100),
+            Verdict::WrongValue {
+                output: (4, 0),
+                index: 1,
+                expected: 4,
+                actual: 5
+            }
+        );
+    }
+
+    #[test]
+    fn run_missing_and_extra_outputs() {
+        let mut game = Game::with(filter_board(vec![3, 0], vec![3, 5]));
+        assert_eq!(
+            game.run_to_completion(100),
+            Verdict::MissingOutputs {
+                output: (4, 0),
+                expected: 2,
+                actual: 1
+            }
+        );
+
+        let mut game = Game::with(filter_board(vec![3, 5], vec![3]));
+        assert_eq!(
+            game.run_to_completion(100),
+            Verdict::ExtraOutputs {
+                output: (4, 0),
+                expected: 1,
+                actual: 2
+            }
+        );
+    }
+
+    #[test]
+    fn run_times_out() {
+        let mut game = Game::with(filter_board(vec![3; 20], vec![3; 20]));
+        assert_eq!(game.run_to_completion(50), Verdict::TimedOut { tick: 50 });
+    }
+
+    #[test]
+    fn run_deadlocks() {
+        let mut board = filter_board(vec![3], vec![3]);
+        board.set_tile(3, 0, Tile::Lock { locked: true });
+        let mut game = Game::with(board);
+        let verdict = game.run_to_completion(50);
+        assert_eq!(
+            verdict,
+            Verdict::Deadlock {
+                tick: 3,
+                locks: vec![(3, 0)]
+            }
+        );
+        assert_eq!(
+            verdict.to_string(),
+            "deadlock at tick 3, signals stuck at (3, 0)"
+        );
+    }
+
+    /// Four wires in a ring, with a signal going round.
+    fn ring() -> Game {
+        let board = Board::from_text("* -*\n|  |\n* -*").unwrap();
+        let mut game = Game::with(board);
+        game.spawn(Signal::new(0, 0, Orientation::East, 1));
+        game
+    }
+
+    #[test]
+    fn run_livelocks() {
+        let mut game = ring();
+        let verdict = game.run_to_completion(1000);
+        assert_eq!(verdict, Verdict::Livelock { tick: 8, period: 4 });
+        assert_eq!(verdict.to_string(), "livelock at tick 8, period 4");
+
+        // Flipflops take part in the state: this one sends the signal round
+        // the short loop (4 ticks), then the long one (8 ticks).
+        let board = Board::from_text(
+            "* -F<-*\n\
+             |  |  |\n\
+             J^-*  *\n\
+             |     |\n\
+             * -* -*",
+        )
+        .unwrap();
+        let mut game = Game::with(board);
+        game.spawn(Signal::new(0, 0, Orientation::East, 1));
+        match game.run_to_completion(1000) {
+            Verdict::Livelock { period, .. } => assert_eq!(period, 12),
+            verdict => panic!("unexpected verdict {:?}", verdict),
+        }
+    }
+
+    #[test]
+    fn spawning_clears_stall() {
+        let mut game = ring();
+        for _ in 0..8 {
+            game.step();
+        }
+        assert!(game.outcome(1000).is_some());
+        game.spawn(Signal::new(1, 1, Orientation::West, 2));
+        assert_eq!(game.outcome(1000), None);
+    }
+
+    /// What can be told of a game from the outside at its current tick.
+    fn snapshot(game: &Game) -> (u64, Vec<Signal>, Board, Option<Vec<u32>>) {
+        (
+            game.tick(),
+            game.signals().to_vec(),
+            game.board().clone(),
+            game.received(4, 0).map(<[u32]>::to_vec),
+        )
+    }
+
+    #[test]
+    fn step_back_and_seek() {
+        // A flipflop's state has to come back too: it sends every other
+        // value to the deleter.
+        let mut board = filter_board((0..60).collect(), vec![]);
+        board.set_tile(
+            2,
+            0,
+            Tile::Flipflop {
+                orientation: Orientation::West,
+                reversed: false,
+            },
+        );
+        let mut game = Game::with(board);
+        let mut snapshots = vec![snapshot(&game)];
+        while game.outcome(1000).is_none() {
+            game.step();
+            snapshots.push(snapshot(&game));
+        }
+        let end = game.tick();
+        assert!(end > 2 * KEYFRAME_INTERVAL);
+
+        for &tick in &[end - 1, 130, 129, 128, 127, 64, 1, 0, 200, 65] {
+            game.seek(tick);
+            assert_eq!(snapshot(&game), snapshots[tick as usize]);
+        }
+        assert!(game.step_back());
+        assert_eq!(snapshot(&game), snapshots[64]);
+        game.seek(0);
+        assert!(!game.step_back());
+        game.seek(end);
+        assert_eq!(game.outcome(1000), Some(game.verdict()));
+        assert_eq!(snapshot(&game), snapshots[end as usize]);
+    }
+
+    #[test]
+    fn seek_keeps_spawned_signals() {
+        let mut game = Game::with(Board::from_text("* -* -* -*").unwrap());
+        game.step();
+        game.spawn(Signal::new(0, 0, Orientation::East, 9));
+        game.step();
+        let after_spawn = snapshot(&game);
+
+        game.seek(0);
+        assert!(game.signals().is_empty());
+        game.seek(2);
+        assert_eq!(snapshot(&game), after_spawn);
+    }
+
+    #[test]
+    fn seeking_back_clears_stall() {
+        let mut game = ring();
+        assert!(matches!(
+            game.run_to_completion(1000),
+            Verdict::Livelock { .. }
+        ));
+        game.seek(3);
+        assert_eq!(game.outcome(1000), None);
+        assert_eq!(
+            game.run_to_completion(1000),
+            Verdict::Livelock { tick: 8, period: 4 }
+        );
+    }
+
+    #[test]
+    fn deadlock_in_sub_board() {
+        let contents = Board::from_text("I0-L -O0\n\ndata 0:").unwrap();
+        let board = sub_board_around(contents, vec![5], vec![5]);
+        let mut game = Game::with(board);
+        match game.run_to_completion(100) {
+            Verdict::Deadlock { locks, .. } => assert_eq!(locks, [(1, 1)]),
+            verdict => panic!("unexpected verdict {:?}", verdict),
+        }
+    }
+
+    /// An incrementer between a west Input port and a north Output port.
+    fn incrementer_board() -> Board {
+        let mut board = Board::default();
+        let empty = board.store_mut().add_data(vec![]);
+        board.set_tile(0, 1, Tile::Input { data: empty });
+        board.set_tile(1, 1, Tile::Incrementer { reversed: false });
+        board.set_tile(
+            1,
+            0,
+            Tile::Output {
+                expected_data: empty,
+            },
+        );
+        board.connect(0, 1, Orientation::East);
+        board.connect(1, 1, Orientation::North);
+        board
+    }
+
+    /// Wraps `contents` in a sub-board fed from the west and feeding the
+    /// output above it.
+    fn sub_board_around(contents: Board, data: Vec<u32>, expected_data: Vec<u32>) -> Board {
+        let mut board = Board::default();
+        let data = board.store_mut().add_data(data);
+        let expected_data = board.store_mut().add_data(expected_data);
+        let contents = board.store_mut().add_sub_board(contents, BoardIcon::Green);
+        board.set_tile(0, 1, Tile::Input { data });
+        board.set_tile(1, 1, Tile::SubBoard { contents });
+        board.set_tile(1, 0, Tile::Output { expected_data });
+        board.connect(0, 1, Orientation::East);
+        board.connect(1, 1, Orientation::North);
+        board
+    }
+
+    #[test]
+    fn sub_board_ports() {
+        let board = incrementer_board();
+        assert_eq!(board.port(Orientation::West), Some((0, 1)));
+        assert_eq!(board.port(Orientation::North), Some((1, 0)));
+        assert_eq!(board.port(Orientation::South), None);
+        assert_eq!(board.port(Orientation::East), None);
+    }
+
+    #[test]
+    fn sub_board_runs() {
+        let board = sub_board_around(incrementer_board(), vec![1, 5], vec![2, 6]);
+        let mut game = Game::with(board);
+        assert_eq!(game.run_to_completion(100), Verdict::Pass);
+    }
+
+    #[test]
+    fn sub_board_timing() {
+        let board = sub_board_around(incrementer_board(), vec![1], vec![2]);
+        let mut game = Game::with(board);
+        // Tick 1: the input sends 1 into the sub-board. Tick 2: it reaches
+        // the incrementer. Tick 3: 2 reaches the Output port.
+        for _ in 0..3 {
+            game.step();
+        }
+        assert_eq!(game.signals(), &[Signal::new(1, 1, Orientation::North, 2)]);
+        assert_eq!(game.received(1, 0), Some(&[][..]));
+        // Tick 4: it leaves the sub-board tile for the output above.
+        game.step();
+        assert_eq!(game.received(1, 0), Some(&[2][..]));
+        assert!(game.is_finished());
+    }
+
+    #[test]
+    fn sub_board_without_port() {
+        // Nothing inside takes the signals entering from the west.
+        let mut contents = incrementer_board();
+        contents.clear_tile(0, 1);
+        let board = sub_board_around(contents, vec![1], vec![2]);
+        let mut game = Game::with(board);
+        let mut events = Vec::new();
+        game.step_traced(&mut events);
+        assert!(events.contains(&Event::Deleted {
+            tick: 1,
+            at: (1, 1),
+            value: 1
+        }));
+        assert!(game.is_idle());
+    }
+
+    #[test]
+    fn nested_sub_boards_run() {
+        let mut contents = incrementer_board();
+        for _ in 0..3 {
+            contents = sub_board_around(contents, vec![], vec![]);
+        }
+        let board = sub_board_around(contents, vec![0, 7], vec![1, 8]);
+        let mut game = Game::with(board);
+        assert_eq!(game.run_to_completion(200), Verdict::Pass);
+    }
+}
@@ -0,0 +1,490 @@
+use crate::array2d::Array2D;
+use crate::{Board, Connection, Tile};
+
+/// The layout of a whole board, before or after it got resized.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    tiles: Array2D<Tile>,
+    connections_v: Array2D<Connection>,
+    connections_h: Array2D<Connection>,
+    width: usize,
+    height: usize,
+}
+
+impl Snapshot {
+    fn of(board: &Board) -> Snapshot {
+        Snapshot {
+            tiles: board.tiles.clone(),
+            connections_v: board.connections_v.clone(),
+            connections_h: board.connections_h.clone(),
+            width: board.width,
+            height: board.height,
+        }
+    }
+
+    fn restore(&self, board: &mut Board) {
+        board.tiles = self.tiles.clone();
+        board.connections_v = self.connections_v.clone();
+        board.connections_h = self.connections_h.clone();
+        board.width = self.width;
+        board.height = self.height;
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Change {
+    Tile {
+        x: usize,
+        y: usize,
+        before: Tile,
+        after: Tile,
+    },
+    Connection {
+        horizontal: bool,
+        x: usize,
+        y: usize,
+        before: Connection,
+        after: Connection,
+    },
+    Resize {
+        before: Box<Snapshot>,
+        after: Box<Snapshot>,
+    },
+}
+
+impl Change {
+    /// Fills in what the touched cell holds after the edit. Returns `None`
+    /// if it ended up unchanged.
+    fn settle(self, board: &Board) -> Option<Change> {
+        let change = match self {
+            Change::Tile { x, y, before, .. } => Change::Tile {
+                x,
+                y,
+                before,
+                after: *board.tiles.get(x, y)?,
+            },
+            Change::Connection {
+                horizontal,
+                x,
+                y,
+                before,
+                ..
+            } => {
+                let connections = if horizontal {
+                    &board.connections_h
+                } else {
+                    &board.connections_v
+                };
+                Change::Connection {
+                    horizontal,
+                    x,
+                    y,
+                    before,
+                    after: *connections.get(x, y)?,
+                }
+            }
+            Change::Resize { before, .. } => Change::Resize {
+                before,
+                after: Box::new(Snapshot::of(board)),
+            },
+        };
+        match &change {
+            Change::Tile { before, after, .. } if before == after => None,
+            Change::Connection { before, after, .. } if before == after => None,
+            _ => Some(change),
+        }
+    }
+
+    fn apply(&self, board: &mut Board, undo: bool) {
+        match self {
+            Change::Tile {
+                x,
+                y,
+                before,
+                after,
+            } => {
+                if let Some(tile) = board.tiles.get_mut(*x, *y) {
+                    *tile = if undo { *before } else { *after };
+                }
+            }
+            Change::Connection {
+                horizontal,
+                x,
+                y,
+                before,
+                after,
+            } => {
+                let connections = if *horizontal {
+                    &mut board.connections_h
+                } else {
+                    &mut board.connections_v
+                };
+                if let Some(conn) = connections.get_mut(*x, *y) {
+                    *conn = if undo { *before } else { *after };
+                }
+            }
+            Change::Resize { before, after } => {
+                if undo {
+                    before.restore(board)
+                } else {
+                    after.restore(board)
+                }
+            }
+        }
+    }
+}
+
+/// One undoable step: a single edit, or a group of them.
+#[derive(Debug, Clone, Default)]
+struct Command {
+    changes: Vec<Change>,
+}
+
+/// The edits made to a [`Board`], so they can be undone and redone.
+///
+/// Edits note the cells they are about to change, so recording one only
+/// costs as much as the cells it touches. Resizing is the exception, and
+/// keeps a copy of the whole board before and after.
+///
+/// Edits to sub-boards and additions to the [`crate::BoardStore`] aren't
+/// recorded: the store never drops anything, so the ids tiles refer to stay
+/// valid whatever gets undone.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    undo: Vec<Command>,
+    redo: Vec<Command>,
+    /// The group being recorded, if any.
+    group: Option<Command>,
+    /// Set while an edit is in progress, so that edits made by other edits
+    /// (like `clear_tile` disconnecting the tile) get recorded as one.
+    editing: bool,
+    /// Set while edits shouldn't be recorded at all, see [`History::skip`].
+    skipping: bool,
+    /// The cells the edit in progress touched, with what they held before.
+    touched: Vec<Change>,
+    /// What the edit in progress changed before resizing the board, see
+    /// [`History::touch_all`].
+    settled: Vec<Change>,
+}
+
+impl History {
+    /// Runs `edit` on `board`, recording what it changed as one command.
+    pub(crate) fn record(board: &mut Board, edit: impl FnOnce(&mut Board)) {
+        if board.history.editing || board.history.skipping {
+            edit(board);
+            return;
+        }
+
+        board.history.editing = true;
+        edit(board);
+        board.history.editing = false;
+
+        let touched = std::mem::take(&mut board.history.touched);
+        let mut changes = std::mem::take(&mut board.history.settled);
+        changes.extend(
+            touched
+                .into_iter()
+                .filter_map(|change| change.settle(board)),
+        );
+        if changes.is_empty() {
+            return;
+        }
+        let history = &mut board.history;
+        history.redo.clear();
+        match &mut history.group {
+            Some(group) => group.changes.extend(changes),
+            None => history.undo.push(Command { changes }),
+        }
+    }
+
+    /// Runs `edit` on `board` without recording it, for edits that aren't
+    /// the player's to undo.
+    pub(crate) fn skip(board: &mut Board, edit: impl FnOnce(&mut Board)) {
+        let skipping = std::mem::replace(&mut board.history.skipping, true);
+        edit(board);
+        board.history.skipping = skipping;
+    }
+
+    /// Notes that the edit in progress, if any, is about to change the tile
+    /// at `x`, `y`, which holds `tile`.
+    pub(crate) fn touch_tile(&mut self, x: usize, y: usize, tile: Tile) {
+        if !self.editing {
+            return;
+        }
+        let touched = self.touched.iter().any(|change| match change {
+            Change::Tile { x: tx, y: ty, .. } => (*tx, *ty) == (x, y),
+            _ => false,
+        });
+        if !touched {
+            self.touched.push(Change::Tile {
+                x,
+                y,
+                before: tile,
+                after: tile,
+            });
+        }
+    }
+
+    /// Notes that the edit in progress, if any, is about to change a
+    /// connection, given by its position in `connections_h` or
+    /// `connections_v`.
+    pub(crate) fn touch_connection(
+        &mut self,
+        horizontal: bool,
+        x: usize,
+        y: usize,
+        connection: Connection,
+    ) {
+        if !self.editing {
+            return;
+        }
+        let touched = self.touched.iter().any(|change| match change {
+            Change::Connection {
+                horizontal: th,
+                x: tx,
+                y: ty,
+                ..
+            } => (*th, *tx, *ty) == (horizontal, x, y),
+            _ => false,
+        });
+        if !touched {
+            self.touched.push(Change::Connection {
+                horizontal,
+                x,
+                y,
+                before: connection,
+                after: connection,
+            });
+        }
+    }
+
+    /// Notes that the edit in progress, if any, is about to change the
+    /// whole of `board`.
+    pub(crate) fn touch_all(board: &mut Board) {
+        if !board.history.editing {
+            return;
+        }
+        // The cells touched so far may not survive the resize: what they
+        // hold now is their state after the edit, and undoing the resize
+        // brings them back before they are undone.
+        let touched = std::mem::take(&mut board.history.touched);
+        let settled: Vec<Change> = touched
+            .into_iter()
+            .filter_map(|change| change.settle(board))
+            .collect();
+        board.history.settled.extend(settled);
+        let before = Box::new(Snapshot::of(board));
+        board.history.touched.push(Change::Resize {
+            after: before.clone(),
+            before,
+        });
+    }
+
+    pub(crate) fn begin_group(&mut self) {
+        if self.group.is_none() {
+            self.group = Some(Command::default());
+        }
+    }
+
+    pub(crate) fn end_group(&mut self) {
+        if let Some(group) = self.group.take() {
+            if !group.changes.is_empty() {
+                self.undo.push(group);
+            }
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub(crate) fn undo(board: &mut Board) -> bool {
+        board.history.end_group();
+        let command = match board.history.undo.pop() {
+            Some(command) => command,
+            None => return false,
+        };
+        for change in command.changes.iter().rev() {
+            change.apply(board, true);
+        }
+        board.history.redo.push(command);
+        true
+    }
+
+    pub(crate) fn redo(board: &mut Board) -> bool {
+        board.history.end_group();
+        let command = match board.history.redo.pop() {
+            Some(command) => command,
+            None => return false,
+        };
+        for change in &command.changes {
+            change.apply(board, false);
+        }
+        board.history.undo.push(command);
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Board, History, Orientation, Tile};
+
+    fn wire() -> Tile {
+        Tile::Wire { slow: false }
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut board = Board::default();
+        let empty = board.clone();
+        board.set_tile(0, 0, wire());
+        board.set_tile(1, 0, wire());
+        let placed = board.clone();
+        board.connect(0, 0, Orientation::East);
+        let connected = board.clone();
+
+        assert!(board.undo());
+        assert_eq!(board, placed);
+        assert!(board.undo());
+        assert!(board.undo());
+        assert_eq!(board, empty);
+        assert!(!board.undo());
+
+        assert!(board.redo());
+        assert!(board.redo());
+        assert!(board.redo());
+        assert_eq!(board, connected);
+        assert!(!board.redo());
+    }
+
+    #[test]
+    fn clones_start_without_history() {
+        let mut board = Board::default();
+        board.set_tile(0, 0, wire());
+        let mut copy = board.clone();
+        assert_eq!(copy, board);
+        assert!(!copy.history().can_undo());
+        assert!(!copy.undo());
+        assert!(board.undo());
+    }
+
+    #[test]
+    fn edits_drop_redo() {
+        let mut board = Board::default();
+        board.set_tile(0, 0, wire());
+        board.undo();
+        board.set_tile(1, 0, wire());
+        assert!(!board.history().can_redo());
+        assert_eq!(board.get_tile(0, 0), Some(&Tile::Empty));
+    }
+
+    #[test]
+    fn edits_record_only_what_they_change() {
+        let mut board = Board::new(64, 64);
+        board.set_tile(0, 0, wire());
+        board.set_tile(1, 0, wire());
+        board.connect(0, 0, Orientation::East);
+        board.set_tile(1, 0, wire());
+        let changes: Vec<usize> = board
+            .history
+            .undo
+            .iter()
+            .map(|command| command.changes.len())
+            .collect();
+        assert_eq!(changes, [1, 1, 1]);
+    }
+
+    #[test]
+    fn side_effects_are_undone() {
+        let mut board = Board::default();
+        board.set_tile(
+            1,
+            1,
+            Tile::Joiner {
+                orientation: Orientation::North,
+            },
+        );
+        for &(x, y) in &[(0, 1), (2, 1), (1, 2)] {
+            board.set_tile(x, y, wire());
+        }
+        board.connect(1, 1, Orientation::West);
+        board.connect(1, 1, Orientation::East);
+        let before = board.clone();
+
+        // Clearing the joiner disconnects it too, all in one step.
+        board.clear_tile(1, 1);
+        assert!(board.undo());
+        assert_eq!(board, before);
+        assert_eq!(
+            board.get_tile(1, 1),
+            Some(&Tile::Joiner {
+                orientation: Orientation::East
+            })
+        );
+    }
+
+    #[test]
+    fn groups() {
+        let mut board = Board::default();
+        let empty = board.clone();
+        board.begin_group();
+        for x in 0..4 {
+            board.set_tile(x, 0, wire());
+            if x > 0 {
+                board.connect(x - 1, 0, Orientation::East);
+            }
+        }
+        board.end_group();
+        let dragged = board.clone();
+
+        assert!(board.undo());
+        assert_eq!(board, empty);
+        assert!(board.redo());
+        assert_eq!(board, dragged);
+    }
+
+    #[test]
+    fn resize() {
+        let mut board = Board::default();
+        board.set_tile(7, 7, wire());
+        let before = board.clone();
+        board.resize(4, 4);
+        assert!(board.undo());
+        assert_eq!(board, before);
+        assert!(board.redo());
+        assert_eq!((board.width(), board.height()), (4, 4));
+    }
+
+    #[test]
+    fn edit_then_resize() {
+        let mut board = Board::default();
+        let before = board.clone();
+        board.begin_group();
+        board.set_tile(1, 1, wire());
+        board.set_tile(6, 6, wire());
+        board.resize(4, 4);
+        board.end_group();
+        let after = board.clone();
+        assert!(board.undo());
+        assert_eq!(board, before);
+        assert!(board.redo());
+        assert_eq!(board, after);
+
+        // Likewise when a single edit resizes the board after touching it.
+        board.undo();
+        History::record(&mut board, |board| {
+            board.set_tile(1, 1, wire());
+            board.set_tile(6, 6, wire());
+            board.resize(4, 4);
+        });
+        assert_eq!(board, after);
+        assert!(board.undo());
+        assert_eq!(board, before);
+        assert!(board.redo());
+        assert_eq!(board, after);
+    }
+}
@@ -0,0 +1,898 @@
+//! Puzzles: a board to build on, the tiles allowed on it, and the data a
+//! solution is tested with.
+//!
+//! Levels are written in text, a few `key: value` lines followed by a blank
+//! line and the level's board in the format of [`crate::text`]:
+//!
+//! ```text
+//! name: Filter
+//! description: Let every value through, except zeroes.
+//! allow: wire sorter deleter
+//! par: 14 cycles, 4 tiles
+//! test: 0 3 0 5 -> 3 5
+//! test: 1 0 2 -> 1 2
+//!
+//! I0 .  .  .  O0
+//!
+//! .  .  .  .  .
+//!
+//! data 0:
+//! ```
+//!
+//! `description` may be repeated, one line each. Every `test` gives the
+//! values of each input, then after `->` those expected from each output.
+//! Inputs and outputs are taken in reading order, and separated by `/` when
+//! there are several: `test: 1 2 / 3 -> 4`. The data of the board's own
+//! inputs and outputs is ignored.
+//!
+//! Levels with a single input and output may also generate random test
+//! cases from a seed, on top of their fixed ones:
+//!
+//! ```text
+//! generate: 20 values from 0 to 99 -> primes
+//! ```
+//!
+//! The expected output is computed by a [`Rule`]: `same`, `primes`,
+//! `non-zero`, `increment` or `decrement`.
+
+use crate::text::{self, ParseError};
+use crate::{Board, Game, History, Orientation, Rng, Score, Tile, TileKind, Verdict};
+use std::fmt;
+use std::ops::Range;
+
+/// The scores a good solution reaches: at most this many cycles and tiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Par {
+    pub cycles: u64,
+    pub tiles: usize,
+}
+
+/// The values fed to a level's inputs, and those its outputs expect, for one
+/// run of a solution. Both are in the reading order of the Input and Output
+/// tiles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCase {
+    pub inputs: Vec<Vec<u32>>,
+    pub outputs: Vec<Vec<u32>>,
+}
+
+/// How a [`Generator`] computes the expected output from the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    Same,
+    /// Keeps the prime values only.
+    Primes,
+    NonZero,
+    /// Adds one to every value, saturating like [`Tile::Incrementer`].
+    Increment,
+    Decrement,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 5] = [
+        Rule::Same,
+        Rule::Primes,
+        Rule::NonZero,
+        Rule::Increment,
+        Rule::Decrement,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::Same => "same",
+            Rule::Primes => "primes",
+            Rule::NonZero => "non-zero",
+            Rule::Increment => "increment",
+            Rule::Decrement => "decrement",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Rule::ALL.iter().copied().find(|rule| rule.name() == name)
+    }
+
+    pub fn apply(&self, input: &[u32]) -> Vec<u32> {
+        let values = input.iter().copied();
+        match self {
+            Rule::Same => values.collect(),
+            Rule::Primes => values.filter(|&value| is_prime(value)).collect(),
+            Rule::NonZero => values.filter(|&value| value != 0).collect(),
+            Rule::Increment => values.map(|value| value.saturating_add(1)).collect(),
+            Rule::Decrement => values.map(|value| value.saturating_sub(1)).collect(),
+        }
+    }
+}
+
+fn is_prime(value: u32) -> bool {
+    value >= 2
+        && (2..)
+            .take_while(|&d: &u64| d * d <= u64::from(value))
+            .all(|d| u64::from(value) % d != 0)
+}
+
+/// Makes random test cases for levels with one input and one output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Generator {
+    /// How many values the input gets.
+    pub count: usize,
+    pub min: u32,
+    pub max: u32,
+    pub rule: Rule,
+}
+
+impl Generator {
+    /// Generates the test case for `seed`. The same seed always gives the
+    /// same test case.
+    pub fn generate(&self, seed: u64) -> TestCase {
+        let mut rng = Rng::new(seed);
+        let input: Vec<u32> = (0..self.count)
+            .map(|_| rng.range(self.min, self.max))
+            .collect();
+        TestCase {
+            outputs: vec![self.rule.apply(&input)],
+            inputs: vec![input],
+        }
+    }
+}
+
+/// Tells which of a level's test cases something happened in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CaseId {
+    /// The test case at this index in [`Level::test_cases`].
+    Fixed(usize),
+    /// The test case made by the level's [`Generator`] from this seed.
+    Seed(u64),
+}
+
+impl fmt::Display for CaseId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CaseId::Fixed(index) => write!(f, "test case {}", index + 1),
+            CaseId::Seed(seed) => write!(f, "seed {}", seed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Level {
+    pub name: String,
+    pub description: String,
+    /// The tiles every solution starts from. Non-empty ones are fixed: the
+    /// player can wire them, but not replace them.
+    pub board: Board,
+    /// The kinds of tiles the player may place, besides the fixed ones.
+    pub allowed: Vec<TileKind>,
+    pub test_cases: Vec<TestCase>,
+    /// Makes more test cases, if the level has a single input and output.
+    pub generator: Option<Generator>,
+    pub par: Option<Par>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LevelError {
+    /// A line before the board that isn't a `key: value` pair.
+    InvalidLine {
+        line: usize,
+    },
+    MissingName,
+    UnknownTileKind {
+        line: usize,
+        name: String,
+    },
+    /// A test case that doesn't parse, or doesn't have as many inputs and
+    /// outputs as the board.
+    InvalidTestCase {
+        line: usize,
+    },
+    /// A generator that doesn't parse, or for a board without exactly one
+    /// input and one output.
+    InvalidGenerator {
+        line: usize,
+    },
+    NoTestCases,
+    Board(ParseError),
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelError::InvalidLine { line } => write!(f, "line {}: invalid line", line),
+            LevelError::MissingName => write!(f, "the level has no name"),
+            LevelError::UnknownTileKind { line, name } => {
+                write!(f, "line {}: unknown tile kind `{}`", line, name)
+            }
+            LevelError::InvalidTestCase { line } => {
+                write!(f, "line {}: invalid test case", line)
+            }
+            LevelError::InvalidGenerator { line } => {
+                write!(f, "line {}: invalid generator", line)
+            }
+            LevelError::NoTestCases => write!(f, "the level has no test cases"),
+            LevelError::Board(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+/// Why a solution can't be played on a level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    WrongSize {
+        expected: (usize, usize),
+        actual: (usize, usize),
+    },
+    /// The solution replaced the fixed tile at this position.
+    FixedTile { x: usize, y: usize },
+    /// The solution uses a tile the level doesn't allow at this position,
+    /// possibly inside a sub-board.
+    NotAllowed { x: usize, y: usize, kind: TileKind },
+    /// The sub-board at this position, or one nested in it, holds an input
+    /// or output that isn't one of its ports, see [`Board::port`].
+    StrayPort { x: usize, y: usize },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::WrongSize { expected, actual } => write!(
+                f,
+                "the solution is {}x{}, the level {}x{}",
+                actual.0, actual.1, expected.0, expected.1
+            ),
+            Violation::FixedTile { x, y } => {
+                write!(f, "the fixed tile at ({}, {}) was replaced", x, y)
+            }
+            Violation::NotAllowed { x, y, kind } => {
+                write!(f, "{} at ({}, {}) isn't allowed", kind.name(), x, y)
+            }
+            Violation::StrayPort { x, y } => write!(
+                f,
+                "the sub-board at ({}, {}) has an input or output away from its ports",
+                x, y
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Violation {}
+
+/// Why a solution didn't make it through a level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+    Invalid(Violation),
+    /// The first test case the solution failed.
+    Case {
+        case: CaseId,
+        verdict: Verdict,
+    },
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::Invalid(violation) => violation.fmt(f),
+            Failure::Case { case, verdict } => write!(f, "{}: {}", case, verdict),
+        }
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// The positions of the tiles of a given kind, in reading order.
+fn positions(board: &Board, kind: TileKind) -> Vec<(usize, usize)> {
+    board
+        .tiles
+        .iter()
+        .filter(|(_, _, tile)| tile.kind() == kind)
+        .map(|(x, y, _)| (x, y))
+        .collect()
+}
+
+/// Whether `tile` is a sub-board holding an input or output that isn't one
+/// of its ports, looking into nested sub-boards too. Nothing would ever come
+/// out of such an input, and signals reaching such an output would vanish.
+fn has_stray_port(board: &Board, tile: &Tile) -> bool {
+    let contents = match tile {
+        Tile::SubBoard { contents } => board.store().sub_board(*contents),
+        _ => None,
+    };
+    let contents = match contents {
+        Some(sub_board) => &sub_board.contents,
+        None => return false,
+    };
+    let ports: Vec<_> = Orientation::BY_PRIORITY
+        .iter()
+        .filter_map(|&side| contents.port(side))
+        .collect();
+    contents.tiles.iter().any(|(x, y, tile)| match tile {
+        Tile::Input { .. } | Tile::Output { .. } => !ports.contains(&(x, y)),
+        tile => has_stray_port(contents, tile),
+    })
+}
+
+impl Level {
+    pub fn width(&self) -> usize {
+        self.board.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.board.height()
+    }
+
+    /// Whether the tile at this position is fixed by the level.
+    pub fn is_fixed(&self, x: usize, y: usize) -> bool {
+        !matches!(self.board.get_tile(x, y), Some(Tile::Empty) | None)
+    }
+
+    /// Checks that `solution` only builds on the level with allowed tiles.
+    /// At fixed positions, it may hold nothing or a tile of the same kind:
+    /// the level's tile is put back there anyway, see [`Level::prepare`].
+    pub fn validate(&self, solution: &Board) -> Result<(), Violation> {
+        let expected = (self.width(), self.height());
+        let actual = (solution.width(), solution.height());
+        if expected != actual {
+            return Err(Violation::WrongSize { expected, actual });
+        }
+
+        for (x, y, tile) in &solution.tiles {
+            let kind = tile.kind();
+            if self.is_fixed(x, y) {
+                if kind != TileKind::Empty
+                    && Some(kind) != self.board.get_tile(x, y).map(Tile::kind)
+                {
+                    return Err(Violation::FixedTile { x, y });
+                }
+            } else if let Some(kind) = self.forbidden_kind(solution, tile) {
+                return Err(Violation::NotAllowed { x, y, kind });
+            } else if has_stray_port(solution, tile) {
+                return Err(Violation::StrayPort { x, y });
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds a kind the level doesn't allow in `tile`, looking into
+    /// sub-boards too. Their inputs and outputs are ports, which are always
+    /// allowed.
+    fn forbidden_kind(&self, board: &Board, tile: &Tile) -> Option<TileKind> {
+        let kind = tile.kind();
+        if kind != TileKind::Empty && !self.allowed.contains(&kind) {
+            return Some(kind);
+        }
+        if let Tile::SubBoard { contents } = tile {
+            let contents = match board.store().sub_board(*contents) {
+                Some(sub_board) => &sub_board.contents,
+                None => return None,
+            };
+            for (_, _, tile) in &contents.tiles {
+                if let Tile::Input { .. } | Tile::Output { .. } = tile {
+                    continue;
+                }
+                if let Some(kind) = self.forbidden_kind(contents, tile) {
+                    return Some(kind);
+                }
+            }
+        }
+        None
+    }
+
+    /// Builds the board to run `solution` on for a test case: the fixed
+    /// tiles are put over the solution, inputs and outputs holding the test
+    /// case's data. The solution should have been validated first.
+    pub fn prepare(&self, solution: &Board, test_case: &TestCase) -> Board {
+        let mut board = solution.clone();
+        let mut inputs = test_case.inputs.iter();
+        let mut outputs = test_case.outputs.iter();
+        for (x, y, tile) in &self.board.tiles {
+            let tile = match *tile {
+                Tile::Empty => continue,
+                Tile::SubBoard { contents } => {
+                    let sub_board = match self.board.store().sub_board(contents) {
+                        Some(sub_board) => sub_board.clone(),
+                        None => continue,
+                    };
+                    let contents = board
+                        .store_mut()
+                        .add_sub_board(sub_board.contents, sub_board.icon);
+                    Tile::SubBoard { contents }
+                }
+                Tile::Input { .. } => {
+                    let data = inputs.next().cloned().unwrap_or_default();
+                    Tile::Input {
+                        data: board.store_mut().add_data(data),
+                    }
+                }
+                Tile::Output { .. } => {
+                    let data = outputs.next().cloned().unwrap_or_default();
+                    Tile::Output {
+                        expected_data: board.store_mut().add_data(data),
+                    }
+                }
+                tile => tile,
+            };
+            // The fixed tiles aren't part of the solution's edits.
+            History::skip(&mut board, |board| board.set_tile(x, y, tile));
+        }
+        board
+    }
+
+    /// The test cases to check solutions against: the fixed ones, then one
+    /// for each seed if the level has a generator.
+    pub fn cases(&self, seeds: Range<u64>) -> Vec<(CaseId, TestCase)> {
+        let fixed = self.test_cases.iter().cloned().enumerate();
+        let mut cases: Vec<_> = fixed.map(|(i, case)| (CaseId::Fixed(i), case)).collect();
+        if let Some(generator) = &self.generator {
+            cases.extend(seeds.map(|seed| (CaseId::Seed(seed), generator.generate(seed))));
+        }
+        cases
+    }
+
+    /// Validates `solution` and runs it on every test case, see
+    /// [`Level::cases`], giving up on a case after `max_ticks`. `on_tick` is
+    /// called after each tick. Returns the solution's score, or the first
+    /// case that failed.
+    pub fn run(
+        &self,
+        solution: &Board,
+        seeds: Range<u64>,
+        max_ticks: u64,
+        mut on_tick: impl FnMut(CaseId, &Game),
+    ) -> Result<Score, Failure> {
+        self.validate(solution).map_err(Failure::Invalid)?;
+        let mut ticks = Vec::new();
+        for (case, test_case) in self.cases(seeds) {
+            let mut game = Game::with(self.prepare(solution, &test_case));
+            let verdict = loop {
+                if let Some(verdict) = game.outcome(max_ticks) {
+                    break verdict;
+                }
+                game.step();
+                on_tick(case, &game);
+            };
+            if verdict != Verdict::Pass {
+                return Err(Failure::Case { case, verdict });
+            }
+            ticks.push(game.tick());
+        }
+        Ok(Score::new(self, solution, &ticks))
+    }
+
+    /// Parses a level written in the format described in [`crate::level`].
+    pub fn from_text(text: &str) -> Result<Level, LevelError> {
+        let lines: Vec<&str> = text.lines().collect();
+        let header_len = lines
+            .iter()
+            .position(|line| line.trim().is_empty())
+            .unwrap_or(lines.len());
+        let board_lines = lines.get(header_len + 1..).unwrap_or(&[]);
+        let board = text::parse_board(board_lines, header_len + 2).map_err(LevelError::Board)?;
+        let input_count = positions(&board, TileKind::Input).len();
+        let output_count = positions(&board, TileKind::Output).len();
+
+        let mut level = Level {
+            name: String::new(),
+            description: String::new(),
+            board,
+            allowed: Vec::new(),
+            test_cases: Vec::new(),
+            generator: None,
+            par: None,
+        };
+        for (row, line) in lines[..header_len].iter().enumerate() {
+            let line_number = row + 1;
+            let invalid = LevelError::InvalidLine { line: line_number };
+            let (key, value) = match line.find(':') {
+                Some(colon) => (&line[..colon], line[colon + 1..].trim()),
+                None => return Err(invalid),
+            };
+            match key {
+                "name" => level.name = value.to_string(),
+                "description" => {
+                    if !level.description.is_empty() {
+                        level.description.push('\n');
+                    }
+                    level.description.push_str(value);
+                }
+                "allow" => {
+                    for name in value.split_whitespace() {
+                        let kind = TileKind::from_name(name).ok_or_else(|| {
+                            LevelError::UnknownTileKind {
+                                line: line_number,
+                                name: name.to_string(),
+                            }
+                        })?;
+                        level.allowed.push(kind);
+                    }
+                }
+                "par" => level.par = Some(parse_par(value).ok_or(invalid)?),
+                "test" => {
+                    let test_case = parse_test_case(value)
+                        .filter(|case| {
+                            case.inputs.len() == input_count && case.outputs.len() == output_count
+                        })
+                        .ok_or(LevelError::InvalidTestCase { line: line_number })?;
+                    level.test_cases.push(test_case);
+                }
+                "generate" => {
+                    let generator = parse_generator(value)
+                        .filter(|_| input_count == 1 && output_count == 1)
+                        .ok_or(LevelError::InvalidGenerator { line: line_number })?;
+                    level.generator = Some(generator);
+                }
+                _ => return Err(invalid),
+            }
+        }
+
+        if level.name.is_empty() {
+            return Err(LevelError::MissingName);
+        }
+        if level.test_cases.is_empty() && level.generator.is_none() {
+            return Err(LevelError::NoTestCases);
+        }
+        Ok(level)
+    }
+}
+
+/// Parses `14 cycles, 4 tiles`.
+fn parse_par(text: &str) -> Option<Par> {
+    let mut cycles = None;
+    let mut tiles = None;
+    for part in text.split(',') {
+        match part.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [n, "cycles"] => cycles = Some(n.parse().ok()?),
+            [n, "tiles"] => tiles = Some(n.parse().ok()?),
+            _ => return None,
+        }
+    }
+    Some(Par {
+        cycles: cycles?,
+        tiles: tiles?,
+    })
+}
+
+/// Parses `20 values from 0 to 99 -> primes`.
+fn parse_generator(text: &str) -> Option<Generator> {
+    match text.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [count, "values", "from", min, "to", max, "->", rule] => {
+            let generator = Generator {
+                count: count.parse().ok()?,
+                min: min.parse().ok()?,
+                max: max.parse().ok()?,
+                rule: Rule::from_name(rule)?,
+            };
+            Some(generator).filter(|generator| generator.min <= generator.max)
+        }
+        _ => None,
+    }
+}
+
+/// Parses `1 2 / 3 -> 4`.
+fn parse_test_case(text: &str) -> Option<TestCase> {
+    let streams = |text: &str| -> Option<Vec<Vec<u32>>> {
+        if text.trim().is_empty() {
+            return Some(vec![]);
+        }
+        text.split('/')
+            .map(|stream| stream.split_whitespace().map(|n| n.parse().ok()).collect())
+            .collect()
+    };
+    let arrow = text.find("->")?;
+    Some(TestCase {
+        inputs: streams(&text[..arrow])?,
+        outputs: streams(&text[arrow + 2..])?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{BoardIcon, Game, Orientation, Verdict};
+
+    const FILTER: &str = "\
+name: Filter
+description: Let every value through,
+description: except zeroes.
+allow: wire sorter deleter
+par: 14 cycles, 4 tiles
+test: 0 3 0 5 -> 3 5
+test: 1 0 2 -> 1 2
+
+I0 .  .  .  O0
+
+.  .  .  .  .
+
+data 0:
+";
+
+    const SOLUTION: &str = "\
+. -* -S<-* -.
+      |
+.  .  D  .  .
+";
+
+    #[test]
+    fn parses() {
+        let level = Level::from_text(FILTER).unwrap();
+        assert_eq!(level.name, "Filter");
+        assert_eq!(
+            level.description,
+            "Let every value through,\nexcept zeroes."
+        );
+        assert_eq!((level.width(), level.height()), (5, 2));
+        assert_eq!(
+            level.allowed,
+            [TileKind::Wire, TileKind::Sorter, TileKind::Deleter]
+        );
+        assert_eq!(
+            level.par,
+            Some(Par {
+                cycles: 14,
+                tiles: 4
+            })
+        );
+        assert_eq!(
+            level.test_cases[1],
+            TestCase {
+                inputs: vec![vec![1, 0, 2]],
+                outputs: vec![vec![1, 2]],
+            }
+        );
+        assert!(level.is_fixed(4, 0));
+        assert!(!level.is_fixed(3, 0));
+    }
+
+    #[test]
+    fn level_goes_over_solution() {
+        let level = Level::from_text(FILTER).unwrap();
+        let solution = Board::from_text(SOLUTION).unwrap();
+        assert_eq!(level.validate(&solution), Ok(()));
+        for test_case in &level.test_cases {
+            let board = level.prepare(&solution, test_case);
+            assert!(!board.history().can_undo());
+            let mut game = Game::with(board);
+            assert_eq!(game.run_to_completion(100), Verdict::Pass);
+            assert_eq!(game.received(4, 0), Some(&test_case.outputs[0][..]));
+        }
+        let score = level.run(&solution, 0..0, 100, |_, _| {}).unwrap();
+        assert_eq!(score.worst_cycles, 14);
+        assert_eq!(score.tile_count(), 4);
+    }
+
+    #[test]
+    fn failures() {
+        let level = Level::from_text(FILTER).unwrap();
+        let mut ticks = 0;
+        let failure = level.run(&Board::new(5, 3), 0..0, 100, |_, _| ticks += 1);
+        assert!(matches!(failure, Err(Failure::Invalid(_))));
+        assert_eq!(ticks, 0);
+
+        // Only the second test case expects a 2 it won't get.
+        let level = Level::from_text(&FILTER.replace("-> 1 2", "-> 1 3")).unwrap();
+        let solution = Board::from_text(SOLUTION).unwrap();
+        let failure = level.run(&solution, 0..0, 100, |_, _| {}).unwrap_err();
+        assert!(matches!(
+            failure,
+            Failure::Case {
+                case: CaseId::Fixed(1),
+                verdict: Verdict::WrongValue { .. }
+            }
+        ));
+        let failure = level.run(&solution, 0..0, 3, |_, _| {});
+        assert_eq!(
+            failure,
+            Err(Failure::Case {
+                case: CaseId::Fixed(0),
+                verdict: Verdict::TimedOut { tick: 3 }
+            })
+        );
+    }
+
+    #[test]
+    fn violations() {
+        let level = Level::from_text(FILTER).unwrap();
+        let solution = Board::from_text(SOLUTION).unwrap();
+
+        assert_eq!(
+            level.validate(&Board::new(5, 3)),
+            Err(Violation::WrongSize {
+                expected: (5, 2),
+                actual: (5, 3)
+            })
+        );
+
+        let mut replaced = solution.clone();
+        replaced.set_tile(0, 0, Tile::Wire { slow: false });
+        assert_eq!(
+            level.validate(&replaced),
+            Err(Violation::FixedTile { x: 0, y: 0 })
+        );
+
+        let mut cloner = solution.clone();
+        cloner.set_tile(1, 1, Tile::Cloner);
+        assert_eq!(
+            level.validate(&cloner),
+            Err(Violation::NotAllowed {
+                x: 1,
+                y: 1,
+                kind: TileKind::Cloner
+            })
+        );
+
+        // Sub-boards have to be allowed, and so does what's inside them,
+        // except for their ports.
+        let mut inner = Board::from_text("I0-% -O0\n\ndata 0:").unwrap();
+        let mut nested = solution;
+        let id = nested
+            .store_mut()
+            .add_sub_board(inner.clone(), BoardIcon::Blue);
+        nested.set_tile(3, 1, Tile::SubBoard { contents: id });
+        let mut level = level;
+        assert_eq!(
+            level.validate(&nested),
+            Err(Violation::NotAllowed {
+                x: 3,
+                y: 1,
+                kind: TileKind::SubBoard
+            })
+        );
+        level.allowed.push(TileKind::SubBoard);
+        assert_eq!(level.validate(&nested), Ok(()));
+
+        inner.set_tile(1, 0, Tile::Cloner);
+        inner.connect(1, 0, Orientation::East);
+        nested.store_mut().sub_board_mut(id).unwrap().contents = inner;
+        assert_eq!(
+            level.validate(&nested),
+            Err(Violation::NotAllowed {
+                x: 3,
+                y: 1,
+                kind: TileKind::Cloner
+            })
+        );
+
+        // An output in the middle of a sub-board isn't a port: nothing it
+        // receives would leave the sub-board.
+        let inner = Board::from_text(
+            "I0-% -O0\n\
+             \x20  |\n\
+             .  O0 .\n\
+             \n\
+             .  .  .\n\
+             \n\
+             data 0:",
+        )
+        .unwrap();
+        nested.store_mut().sub_board_mut(id).unwrap().contents = inner;
+        assert_eq!(
+            level.validate(&nested),
+            Err(Violation::StrayPort { x: 3, y: 1 })
+        );
+    }
+
+    #[test]
+    fn rules() {
+        let input = [0, 1, 2, 3, 4, 9, 13, 25, u32::MAX];
+        assert_eq!(Rule::Primes.apply(&input), [2, 3, 13]);
+        assert_eq!(Rule::NonZero.apply(&input).len(), 8);
+        assert_eq!(Rule::Increment.apply(&input)[8], u32::MAX);
+        assert_eq!(Rule::Decrement.apply(&input)[0], 0);
+        assert!(is_prime(4_294_967_291));
+    }
+
+    #[test]
+    fn generated_cases() {
+        let level = Level::from_text(&FILTER.replace(
+            "test: 1 0 2 -> 1 2",
+            "generate: 6 values from 0 to 3 -> non-zero",
+        ))
+        .unwrap();
+        let generator = level.generator.unwrap();
+        assert_eq!(
+            generator,
+            Generator {
+                count: 6,
+                min: 0,
+                max: 3,
+                rule: Rule::NonZero
+            }
+        );
+        assert_eq!(generator.generate(5), generator.generate(5));
+
+        let cases = level.cases(10..13);
+        let ids: Vec<_> = cases.iter().map(|(id, _)| *id).collect();
+        assert_eq!(
+            ids,
+            [
+                CaseId::Fixed(0),
+                CaseId::Seed(10),
+                CaseId::Seed(11),
+                CaseId::Seed(12)
+            ]
+        );
+        let input = &cases[1].1.inputs[0];
+        assert_eq!(input.len(), 6);
+        assert!(input.iter().all(|&value| value <= 3));
+
+        let solution = Board::from_text(SOLUTION).unwrap();
+        let mut seeds = std::collections::HashSet::new();
+        let score = level.run(&solution, 0..200, 1000, |case, _| {
+            seeds.insert(case);
+        });
+        assert!(score.is_ok());
+        assert_eq!(seeds.len(), 201);
+    }
+
+    #[test]
+    fn run_reports_first_failure() {
+        let level = Level::from_text(&FILTER.replace(
+            "test: 1 0 2 -> 1 2",
+            "generate: 4 values from 0 to 9 -> primes",
+        ))
+        .unwrap();
+        // Filtering zeroes out only passes when the generated values are
+        // zeroes and primes.
+        let solution = Board::from_text(SOLUTION).unwrap();
+        let failure = level.run(&solution, 0..100, 1000, |_, _| {}).unwrap_err();
+        let seed = match failure {
+            Failure::Case {
+                case: CaseId::Seed(seed),
+                ..
+            } => seed,
+            failure => panic!("unexpected failure {:?}", failure),
+        };
+        // The seed reproduces the failure on its own, and was the first one.
+        assert!(level
+            .run(&solution, seed..seed + 1, 1000, |_, _| {})
+            .is_err());
+        assert!(level.run(&solution, 0..seed, 1000, |_, _| {}).is_ok());
+    }
+
+    #[test]
+    fn errors() {
+        let without = |key: &str| {
+            FILTER
+                .lines()
+                .filter(|line| !line.starts_with(key))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        assert_eq!(
+            Level::from_text(&without("name")),
+            Err(LevelError::MissingName)
+        );
+        assert_eq!(
+            Level::from_text(&without("test")),
+            Err(LevelError::NoTestCases)
+        );
+        assert_eq!(
+            Level::from_text(&FILTER.replace("deleter", "teleporter")),
+            Err(LevelError::UnknownTileKind {
+                line: 4,
+                name: "teleporter".to_string()
+            })
+        );
+        assert_eq!(
+            Level::from_text(&FILTER.replace("1 0 2 -> 1 2", "1 0 2 / 3 -> 1 2")),
+            Err(LevelError::InvalidTestCase { line: 7 })
+        );
+        assert_eq!(
+            Level::from_text(&FILTER.replace(
+                "test: 1 0 2 -> 1 2",
+                "generate: 6 values from 3 to 0 -> primes"
+            )),
+            Err(LevelError::InvalidGenerator { line: 7 })
+        );
+        assert_eq!(
+            Level::from_text(&FILTER.replace("par: 14 cycles", "par: 14 ticks")),
+            Err(LevelError::InvalidLine { line: 5 })
+        );
+        assert_eq!(
+            Level::from_text(&FILTER.replace("I0 .", "I0 ?")),
+            Err(LevelError::Board(ParseError::InvalidTile {
+                line: 9,
+                cell: "? ".to_string()
+            }))
+        );
+    }
+}
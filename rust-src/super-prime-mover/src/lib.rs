@@ -1,10 +1,32 @@
-// priorities: bas droite gauche haut
-
 mod array2d;
+mod game;
+mod history;
+pub mod level;
+mod rng;
+pub mod save;
+mod score;
+mod store;
+pub mod text;
+pub mod trace;
 use array2d::Array2D;
+pub use game::{Game, Signal, Verdict};
+pub use history::History;
+pub use level::{CaseId, Failure, Generator, Level, LevelError, Par, Rule, TestCase, Violation};
+pub use rng::Rng;
+pub use save::LoadError;
+pub use score::Score;
+pub use store::{BoardStore, DataId, SubBoard, SubBoardId};
+pub use text::{ParseError, TooManyIds};
+pub use trace::{Event, EventSink, JsonLines, Turn};
 
+/// One of the four sides of a tile.
+///
+/// Whenever several sides compete - a tile that may emit on more than one
+/// side, or several signals entering a tile on the same tick - they are
+/// settled in priority order: south, east, west, north. See
+/// [`Orientation::BY_PRIORITY`].
 #[repr(u8)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Orientation {
     South,
     West,
@@ -13,6 +35,25 @@ pub enum Orientation {
 }
 
 impl Orientation {
+    /// Every side, from the highest priority to the lowest.
+    pub const BY_PRIORITY: [Orientation; 4] = [
+        Orientation::South,
+        Orientation::East,
+        Orientation::West,
+        Orientation::North,
+    ];
+
+    /// The rank of this side in [`Orientation::BY_PRIORITY`]. Lower goes
+    /// first.
+    pub fn priority(&self) -> u8 {
+        match self {
+            Self::South => 0,
+            Self::East => 1,
+            Self::West => 2,
+            Self::North => 3,
+        }
+    }
+
     pub fn to_vector(&self) -> (isize, isize) {
         match self {
             Self::North => (0, -1),
@@ -38,9 +79,34 @@ impl Orientation {
             Self::East => Self::West,
         }
     }
+
+    pub fn clockwise(&self) -> Self {
+        match self {
+            Self::North => Self::East,
+            Self::East => Self::South,
+            Self::South => Self::West,
+            Self::West => Self::North,
+        }
+    }
+
+    pub fn counter_clockwise(&self) -> Self {
+        self.clockwise().opposite()
+    }
+
+    /// Looks up this side in a `[north, east, south, west]` array, as returned
+    /// by [`Board::get_connections`].
+    pub fn is_connected_in(&self, connections: [bool; 4]) -> bool {
+        let [north, east, south, west] = connections;
+        match self {
+            Self::North => north,
+            Self::East => east,
+            Self::South => south,
+            Self::West => west,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BoardIcon {
     Green,
     Red,
@@ -52,15 +118,9 @@ pub enum BoardIcon {
 // Wire { wire1: South, wire2: North }
 //
 
-// TODO: Find a way to make Tile more lightweight.
-// BODY: It'd be nice if Tile was Copy, as that'd make manipulation a lot
-// BODY: easier. The problem is, the Board subtype has a lot of heavy
-// BODY: information in it.
-// BODY:
-// BODY: A potential solution is to have Board contain a simple index to the
-// BODY: subboard, and keep the subboards in a separate vector. The subboards
-// BODY: would never get evicted (as they're necessary to handle undo anyways).
-#[derive(Debug, Clone)]
+/// A tile of a [`Board`]. Heavy payloads (sub-boards, Input and Output data)
+/// live in the board's [`BoardStore`], which keeps Tile small and `Copy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Tile {
     Empty,
     Unusable {
@@ -71,50 +131,161 @@ pub enum Tile {
     },
     Wire {
         // wire1, wire2
+        /// If true, signals stay on this wire for [`SLOW_WIRE_DELAY`] extra
+        /// ticks.
         slow: bool,
     },
     Bridge,
     Joiner {
+        /// The side signals leave through. Follows the newest connection.
         orientation: Orientation,
     },
     Cloner,
     Sorter {
+        /// The side signals come in from. Follows the oldest connection.
         orientation: Orientation,
         reversed: bool,
     },
     Deleter,
     Flipflop {
+        /// The side signals come in from. Follows the oldest connection.
         orientation: Orientation,
+        /// Whether the next signal takes the second output. Flips with every
+        /// signal coming in from `orientation`, even one that is consumed
+        /// because that output isn't wired.
         reversed: bool,
     },
     Incrementer {
         reversed: bool,
     },
     Button {
+        /// The side signals come in from. Follows the oldest connection.
         orientation: Orientation,
     },
     Lock {
         locked: bool,
     },
+    /// A whole board packaged as a single tile. Each side of the tile is
+    /// wired to a port of its contents, see [`Board::port`].
     SubBoard {
-        contents: Board,
-        icon: BoardIcon,
+        contents: SubBoardId,
     },
     Input {
-        data: Vec<u32>,
+        data: DataId,
     },
     Output {
-        expected_data: Vec<u32>,
+        expected_data: DataId,
     },
 }
 
+// Not derived: `#[default]` on an enum variant needs Rust 1.62, past the
+// crate's `rust-version`.
 impl Default for Tile {
     fn default() -> Self {
         Self::Empty
     }
 }
 
+/// The kind of a [`Tile`], without its state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TileKind {
+    Empty,
+    Unusable,
+    Wire,
+    Bridge,
+    Joiner,
+    Cloner,
+    Sorter,
+    Deleter,
+    Flipflop,
+    Incrementer,
+    Button,
+    Lock,
+    SubBoard,
+    Input,
+    Output,
+}
+
+impl TileKind {
+    pub const ALL: [TileKind; 15] = [
+        TileKind::Empty,
+        TileKind::Unusable,
+        TileKind::Wire,
+        TileKind::Bridge,
+        TileKind::Joiner,
+        TileKind::Cloner,
+        TileKind::Sorter,
+        TileKind::Deleter,
+        TileKind::Flipflop,
+        TileKind::Incrementer,
+        TileKind::Button,
+        TileKind::Lock,
+        TileKind::SubBoard,
+        TileKind::Input,
+        TileKind::Output,
+    ];
+
+    /// A lowercase name for the kind, as used in level files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TileKind::Empty => "empty",
+            TileKind::Unusable => "unusable",
+            TileKind::Wire => "wire",
+            TileKind::Bridge => "bridge",
+            TileKind::Joiner => "joiner",
+            TileKind::Cloner => "cloner",
+            TileKind::Sorter => "sorter",
+            TileKind::Deleter => "deleter",
+            TileKind::Flipflop => "flipflop",
+            TileKind::Incrementer => "incrementer",
+            TileKind::Button => "button",
+            TileKind::Lock => "lock",
+            TileKind::SubBoard => "sub-board",
+            TileKind::Input => "input",
+            TileKind::Output => "output",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<TileKind> {
+        TileKind::ALL
+            .iter()
+            .copied()
+            .find(|kind| kind.name() == name)
+    }
+}
+
 impl Tile {
+    pub fn kind(&self) -> TileKind {
+        match self {
+            Tile::Empty => TileKind::Empty,
+            Tile::Unusable { .. } => TileKind::Unusable,
+            Tile::Wire { .. } => TileKind::Wire,
+            Tile::Bridge => TileKind::Bridge,
+            Tile::Joiner { .. } => TileKind::Joiner,
+            Tile::Cloner => TileKind::Cloner,
+            Tile::Sorter { .. } => TileKind::Sorter,
+            Tile::Deleter => TileKind::Deleter,
+            Tile::Flipflop { .. } => TileKind::Flipflop,
+            Tile::Incrementer { .. } => TileKind::Incrementer,
+            Tile::Button { .. } => TileKind::Button,
+            Tile::Lock { .. } => TileKind::Lock,
+            Tile::SubBoard { .. } => TileKind::SubBoard,
+            Tile::Input { .. } => TileKind::Input,
+            Tile::Output { .. } => TileKind::Output,
+        }
+    }
+
+    /// The side a directional tile faces, see [`Board::rotate_tile`].
+    pub fn orientation(&self) -> Option<Orientation> {
+        match *self {
+            Tile::Joiner { orientation }
+            | Tile::Sorter { orientation, .. }
+            | Tile::Flipflop { orientation, .. }
+            | Tile::Button { orientation } => Some(orientation),
+            _ => None,
+        }
+    }
+
     pub fn max_connections(&self) -> usize {
         match self {
             Tile::Button { .. } => 2,
@@ -133,9 +304,154 @@ impl Tile {
             _ => 0,
         }
     }
+
+    /// The number of extra ticks a signal emitted by this tile waits before
+    /// leaving it.
+    pub fn delay(&self) -> u32 {
+        match self {
+            Tile::Wire { slow: true } => SLOW_WIRE_DELAY,
+            _ => 0,
+        }
+    }
+
+    /// Decides what happens to a signal carrying `value` that enters this
+    /// tile through its `from` side. `connections` is the tile's wiring, as
+    /// returned by [`Board::get_connections`].
+    ///
+    /// Stateful tiles (flipflops and locks) update themselves here. Tiles
+    /// with several exits rank their connected sides (other than `from`) by
+    /// [`Orientation::priority`]: the first output is the highest priority
+    /// one.
+    pub fn react(&mut self, from: Orientation, value: u32, connections: [bool; 4]) -> Reaction {
+        match self {
+            Tile::Empty | Tile::Unusable { .. } | Tile::Deleter | Tile::Input { .. } => {
+                Reaction::Consume
+            }
+            Tile::SubBoard { .. } => Reaction::Enter(from, value),
+            Tile::Output { .. } => Reaction::Output(value),
+            Tile::Wire { .. } => pass_through(from, value, connections),
+            Tile::Bridge => {
+                if from.opposite().is_connected_in(connections) {
+                    Reaction::Emit(from.opposite(), value)
+                } else {
+                    Reaction::Consume
+                }
+            }
+            Tile::Incrementer { reversed: false } => {
+                pass_through(from, value.saturating_add(1), connections)
+            }
+            Tile::Incrementer { reversed: true } => {
+                pass_through(from, value.saturating_sub(1), connections)
+            }
+            Tile::Joiner { orientation } => {
+                if from == *orientation || !orientation.is_connected_in(connections) {
+                    Reaction::Consume
+                } else {
+                    Reaction::Emit(*orientation, value)
+                }
+            }
+            Tile::Cloner => match exits(from, connections) {
+                [Some(first), Some(second)] => Reaction::Split([first, second], value),
+                [Some(side), None] => Reaction::Emit(side, value),
+                _ => Reaction::Consume,
+            },
+            Tile::Sorter {
+                orientation,
+                reversed,
+            } => {
+                if from != *orientation {
+                    return Reaction::Consume;
+                }
+                let high = value > SORTER_THRESHOLD;
+                let side = exits(from, connections)[(high != *reversed) as usize];
+                emit_to(side, value)
+            }
+            Tile::Flipflop {
+                orientation,
+                reversed,
+            } => {
+                if from != *orientation {
+                    return Reaction::Consume;
+                }
+                let side = exits(from, connections)[*reversed as usize];
+                *reversed = !*reversed;
+                emit_to(side, value)
+            }
+            Tile::Button { orientation } => {
+                if from != *orientation {
+                    return Reaction::Consume;
+                }
+                match pass_through(from, value, connections) {
+                    Reaction::Emit(side, value) => Reaction::Press(side, value),
+                    reaction => reaction,
+                }
+            }
+            Tile::Lock { locked: true } => Reaction::Block,
+            Tile::Lock { locked } => {
+                *locked = true;
+                pass_through(from, value, connections)
+            }
+        }
+    }
+}
+
+/// How many extra ticks a signal spends on a slow [`Tile::Wire`].
+pub const SLOW_WIRE_DELAY: u32 = 1;
+
+/// Signals strictly above this value leave a non-reversed [`Tile::Sorter`]
+/// through its second output, the others through its first.
+pub const SORTER_THRESHOLD: u32 = 0;
+
+/// What a tile does with a signal entering it. See [`Tile::react`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reaction {
+    /// The signal leaves the tile through the given side.
+    Emit(Orientation, u32),
+    /// The signal is duplicated, one copy leaving through each side.
+    Split([Orientation; 2], u32),
+    /// The signal leaves through the given side after pressing a button,
+    /// which unlocks every lock on the board.
+    Press(Orientation, u32),
+    /// The signal reached an output.
+    Output(u32),
+    /// The signal crosses into a sub-board through the given side.
+    Enter(Orientation, u32),
+    /// The signal is destroyed.
+    Consume,
+    /// The tile refuses the signal for now. It stays where it is and tries
+    /// again on the next tick.
+    Block,
+}
+
+/// Lets a signal go straight through, or around the corner if that's the only
+/// other way out.
+fn pass_through(from: Orientation, value: u32, connections: [bool; 4]) -> Reaction {
+    if from.opposite().is_connected_in(connections) {
+        return Reaction::Emit(from.opposite(), value);
+    }
+    match exits(from, connections) {
+        [Some(side), None] => Reaction::Emit(side, value),
+        _ => Reaction::Consume,
+    }
+}
+
+/// The two highest priority connected sides, other than `from`.
+fn exits(from: Orientation, connections: [bool; 4]) -> [Option<Orientation>; 2] {
+    let mut exits = Orientation::BY_PRIORITY
+        .iter()
+        .copied()
+        .filter(|side| *side != from && side.is_connected_in(connections));
+    [exits.next(), exits.next()]
+}
+
+fn emit_to(side: Option<Orientation>, value: u32) -> Reaction {
+    match side {
+        Some(side) => Reaction::Emit(side, value),
+        None => Reaction::Consume,
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Board {
     tiles: Array2D<Tile>,
     connections_v: Array2D<Connection>,
@@ -143,17 +459,129 @@ pub struct Board {
     width: usize,
     height: usize,
     connection_counter: usize,
+    store: BoardStore,
+    history: History,
+}
+
+/// Clones start with an empty [`History`]: they are mostly made to run a
+/// [`Game`] on, which has no use for the edits that led to the board.
+impl Clone for Board {
+    fn clone(&self) -> Board {
+        Board {
+            tiles: self.tiles.clone(),
+            connections_v: self.connections_v.clone(),
+            connections_h: self.connections_h.clone(),
+            width: self.width,
+            height: self.height,
+            connection_counter: self.connection_counter,
+            store: self.store.clone(),
+            history: History::default(),
+        }
+    }
+}
+
+/// Boards are compared by their contents, regardless of how they were edited.
+impl PartialEq for Board {
+    fn eq(&self, other: &Board) -> bool {
+        self.tiles == other.tiles
+            && self.connections_v == other.connections_v
+            && self.connections_h == other.connections_h
+            && self.width == other.width
+            && self.height == other.height
+            && self.store == other.store
+    }
 }
 
+impl Eq for Board {}
+
 impl Board {
+    /// Creates an empty board of `width` by `height` tiles.
+    pub fn new(width: usize, height: usize) -> Board {
+        Board {
+            tiles: Array2D::new(width, height),
+            // connections_h links a tile to its east neighbor, connections_v
+            // to its south neighbor.
+            connections_h: Array2D::new(width.saturating_sub(1), height),
+            connections_v: Array2D::new(width, height.saturating_sub(1)),
+            connection_counter: 0,
+            width,
+            height,
+            store: BoardStore::default(),
+            history: History::default(),
+        }
+    }
+
+    /// Changes the size of the board, growing or shrinking it from its east
+    /// and south edges. Tiles and connections that still fit are kept.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        History::record(self, |board| {
+            History::touch_all(board);
+            let mut resized = Board::new(width, height);
+            for (x, y, tile) in &board.tiles {
+                if let Some(v) = resized.tiles.get_mut(x, y) {
+                    *v = *tile;
+                }
+            }
+            for (x, y, conn) in &board.connections_h {
+                if let Some(v) = resized.connections_h.get_mut(x, y) {
+                    *v = *conn;
+                }
+            }
+            for (x, y, conn) in &board.connections_v {
+                if let Some(v) = resized.connections_v.get_mut(x, y) {
+                    *v = *conn;
+                }
+            }
+
+            board.tiles = resized.tiles;
+            board.connections_h = resized.connections_h;
+            board.connections_v = resized.connections_v;
+            board.width = width;
+            board.height = height;
+
+            // Tiles on the new edges may have lost connections.
+            for y in 0..height {
+                for x in 0..width {
+                    board.reorient_tile(x, y);
+                }
+            }
+        });
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn store(&self) -> &BoardStore {
+        &self.store
+    }
+
+    pub fn store_mut(&mut self) -> &mut BoardStore {
+        &mut self.store
+    }
+
     pub fn get_tile(&self, x: usize, y: usize) -> Option<&Tile> {
         self.tiles.get(x, y)
     }
 
+    pub(crate) fn get_tile_mut(&mut self, x: usize, y: usize) -> Option<&mut Tile> {
+        self.tiles.get_mut(x, y)
+    }
+
+    /// Places `tile` at `x`, `y`. Connections the new tile can't hold are
+    /// removed, oldest first, as when connecting it.
     pub fn set_tile(&mut self, x: usize, y: usize, tile: Tile) {
-        if let Some(v) = self.tiles.get_mut(x, y) {
-            *v = tile;
-        }
+        History::record(self, |board| {
+            board.touch_tile(x, y);
+            if let Some(v) = board.tiles.get_mut(x, y) {
+                *v = tile;
+                board.update_tile(x, y);
+            }
+        });
     }
 
     pub fn get_connections(&self, x: usize, y: usize) -> [bool; 4] {
@@ -181,101 +609,253 @@ impl Board {
         [north_conn, east_conn, south_conn, west_conn]
     }
 
+    /// Returns whether the tile at `x`, `y` is connected to its neighbor on
+    /// the given side.
+    pub fn is_connected(&self, x: usize, y: usize, side: Orientation) -> bool {
+        side.is_connected_in(self.get_connections(x, y))
+    }
+
+    /// Finds the Input or Output tile acting as this board's port on the
+    /// given side, when it's used as a [`Tile::SubBoard`]: the first one along
+    /// the edge of the board facing that side.
+    ///
+    /// Signals entering the sub-board through a side are sent by the Input
+    /// port on that side, and signals reaching the Output port on a side
+    /// leave the sub-board through it.
+    pub fn port(&self, side: Orientation) -> Option<(usize, usize)> {
+        let is_port = |x: usize, y: usize| match self.get_tile(x, y) {
+            Some(Tile::Input { .. }) | Some(Tile::Output { .. }) => Some((x, y)),
+            _ => None,
+        };
+        let last_x = self.width.saturating_sub(1);
+        let last_y = self.height.saturating_sub(1);
+        match side {
+            Orientation::North => (0..self.width).find_map(|x| is_port(x, 0)),
+            Orientation::South => (0..self.width).find_map(|x| is_port(x, last_y)),
+            Orientation::West => (0..self.height).find_map(|y| is_port(0, y)),
+            Orientation::East => (0..self.height).find_map(|y| is_port(last_x, y)),
+        }
+    }
+
+    fn get_connection(&self, x: usize, y: usize, side: Orientation) -> Option<&Connection> {
+        match side {
+            Orientation::North => self.connections_v.get(x, y.wrapping_sub(1)),
+            Orientation::South => self.connections_v.get(x, y),
+            Orientation::West => self.connections_h.get(x.wrapping_sub(1), y),
+            Orientation::East => self.connections_h.get(x, y),
+        }
+    }
+
+    fn get_mut_connection(
+        &mut self,
+        x: usize,
+        y: usize,
+        side: Orientation,
+    ) -> Option<&mut Connection> {
+        self.touch_connection(x, y, side);
+        // South -> North tile_y - 1
+        match side {
+            Orientation::North => self.connections_v.get_mut(x, y.wrapping_sub(1)),
+            Orientation::South => self.connections_v.get_mut(x, y),
+            Orientation::West => self.connections_h.get_mut(x.wrapping_sub(1), y),
+            Orientation::East => self.connections_h.get_mut(x, y),
+        }
+    }
+
     fn get_mut_connections(&mut self, x: usize, y: usize) -> [Option<&mut Connection>; 4] {
+        for &side in &Orientation::BY_PRIORITY {
+            self.touch_connection(x, y, side);
+        }
         let (north, south) = self.connections_v.get_mut2(x, y.wrapping_sub(1), x, y);
         let (west, east) = self.connections_h.get_mut2(x.wrapping_sub(1), y, x, y);
         [north, east, south, west]
     }
 
     pub fn connect(&mut self, tile_x: usize, tile_y: usize, orientation: Orientation) {
-        // South -> North tile_y - 1
-        let connection = match orientation {
-            Orientation::North => self
-                .connections_v
-                .get_mut(tile_x, tile_y.wrapping_sub(1)),
-            Orientation::South => self.connections_v.get_mut(tile_x, tile_y),
-            Orientation::West => self
-                .connections_h
-                .get_mut(tile_x.wrapping_sub(1), tile_y),
-            Orientation::East => self.connections_h.get_mut(tile_x, tile_y),
-        };
-
-        let connection = match connection {
-            Some(v) => v,
-            None => return
-        };
+        History::record(self, |board| {
+            let timestamp = board.connection_counter;
+            let connection = match board.get_mut_connection(tile_x, tile_y, orientation) {
+                Some(v) => v,
+                None => return,
+            };
 
-        connection.is_connected = true;
-        connection.timestamp = self.connection_counter;
-        self.connection_counter += 1;
+            connection.is_connected = true;
+            connection.timestamp = timestamp;
+            board.connection_counter += 1;
 
-        self.update_tile(tile_x, tile_y);
-        let neightbor_tile = orientation.step(tile_x, tile_y);
-        self.update_tile(neightbor_tile.0, neightbor_tile.1);
+            board.update_tile(tile_x, tile_y);
+            let neightbor_tile = orientation.step(tile_x, tile_y);
+            board.update_tile(neightbor_tile.0, neightbor_tile.1);
+        });
     }
 
-    fn update_tile(&mut self, x: usize, y: usize) {
-        let max_conn = self.tiles.get(x, y).unwrap().max_connections();
-        let mut conns = self.get_mut_connections(x, y);
-        let current_conns = conns
-            .iter()
-            .filter(|conn| match conn {
-                Some(Connection {
-                    is_connected: true, ..
-                }) => true,
-                Some(_) => false,
-                None => false,
-            })
-            .count();
-
-        let need_dc = current_conns.saturating_sub(max_conn);
+    /// Removes the connection between the tile at `tile_x`, `tile_y` and its
+    /// neighbor on the given side.
+    pub fn disconnect(&mut self, tile_x: usize, tile_y: usize, orientation: Orientation) {
+        History::record(self, |board| {
+            match board.get_mut_connection(tile_x, tile_y, orientation) {
+                Some(connection) if connection.is_connected => connection.is_connected = false,
+                _ => return,
+            }
 
-        if need_dc > 0 {
-            conns.sort_by(|c1, c2| {
-                let p1 = match c1 {
-                    Some(Connection { timestamp, is_connected: true }) => *timestamp,
-                    _ => usize::max_value(),
-                };
-                let p2 = match c2 {
-                    Some(Connection { timestamp, is_connected: true }) => timestamp,
-                    _ => &usize::max_value(),
-                };
+            board.update_tile(tile_x, tile_y);
+            let neightbor_tile = orientation.step(tile_x, tile_y);
+            board.update_tile(neightbor_tile.0, neightbor_tile.1);
+        });
+    }
 
-                p1.cmp(p2)
-            });
+    /// Empties the tile at `x`, `y`, along with every connection touching it.
+    pub fn clear_tile(&mut self, x: usize, y: usize) {
+        History::record(self, |board| {
+            for &side in &Orientation::BY_PRIORITY {
+                board.disconnect(x, y, side);
+            }
+            board.set_tile(x, y, Tile::Empty);
+        });
+    }
 
-            for conn in &mut conns[..need_dc] {
-                if let Some(conn) = conn {
-                    conn.is_connected = false;
-                }
+    /// Turns the directional tile at `x`, `y` a quarter turn clockwise,
+    /// keeping its connections. It faces that way until its wiring changes
+    /// and points it back at its connections. Returns false if the tile has
+    /// no orientation.
+    pub fn rotate_tile(&mut self, x: usize, y: usize) -> bool {
+        if self.tiles.get(x, y).and_then(Tile::orientation).is_none() {
+            return false;
+        }
+        History::record(self, |board| {
+            board.touch_tile(x, y);
+            match board.tiles.get_mut(x, y) {
+                Some(Tile::Joiner { orientation })
+                | Some(Tile::Sorter { orientation, .. })
+                | Some(Tile::Flipflop { orientation, .. })
+                | Some(Tile::Button { orientation }) => *orientation = orientation.clockwise(),
+                _ => (),
             }
-            /*
+        });
+        true
+    }
 
-            Sorter's 0 is North
-            FlipFlop's "input" is  North
+    pub fn history(&self) -> &History {
+        &self.history
+    }
 
-            */
+    /// Reverts the last edit, or group of edits. Returns false if there was
+    /// nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        History::undo(self)
+    }
 
-            let conns = self.get_connections(x, y);
-            let tile = self.tiles.get_mut(x, y).unwrap();
-            let new_orientation: Option<Orientation> = match tile {
-                Tile::Sorter{..} | Tile::Flipflop{..} => {
+    /// Re-applies the last undone edit. Returns false if there was nothing to
+    /// redo.
+    pub fn redo(&mut self) -> bool {
+        History::redo(self)
+    }
 
-                    None
-                },
-                _ => {
-                    None
-                }
-            };
+    /// Starts recording edits as a single undo step, until
+    /// [`Board::end_group`] gets called. Useful for drags, which make many
+    /// connections at once.
+    pub fn begin_group(&mut self) {
+        self.history.begin_group();
+    }
+
+    pub fn end_group(&mut self) {
+        self.history.end_group();
+    }
+
+    /// Notes that an edit is about to change the tile at `x`, `y`, see
+    /// [`History::touch_tile`].
+    fn touch_tile(&mut self, x: usize, y: usize) {
+        if let Some(&tile) = self.tiles.get(x, y) {
+            self.history.touch_tile(x, y, tile);
         }
+    }
+
+    /// Notes that an edit is about to change the connection on the given
+    /// side of the tile at `x`, `y`, see [`History::touch_connection`].
+    fn touch_connection(&mut self, x: usize, y: usize, side: Orientation) {
+        let connection = match self.get_connection(x, y, side) {
+            Some(&connection) => connection,
+            None => return,
+        };
+        let (horizontal, x, y) = match side {
+            Orientation::North => (false, x, y.wrapping_sub(1)),
+            Orientation::South => (false, x, y),
+            Orientation::West => (true, x.wrapping_sub(1), y),
+            Orientation::East => (true, x, y),
+        };
+        self.history.touch_connection(horizontal, x, y, connection);
+    }
+
+    fn update_tile(&mut self, x: usize, y: usize) {
+        let max_conn = self.tiles.get(x, y).unwrap().max_connections();
+        let sides = [
+            Orientation::North,
+            Orientation::East,
+            Orientation::South,
+            Orientation::West,
+        ];
+        let mut conns: Vec<_> = sides
+            .iter()
+            .copied()
+            .zip(IntoIterator::into_iter(self.get_mut_connections(x, y)))
+            .filter_map(|(side, conn)| match conn {
+                Some(conn) if conn.is_connected => Some((side, conn)),
+                _ => None,
+            })
+            .collect();
 
         // check max number of connections
         // remove Max(0, max - nconn) oldest connections
-        // TODO: re-orient tile if need be
+        let need_dc = conns.len().saturating_sub(max_conn);
+        conns.sort_by_key(|(_, conn)| conn.timestamp);
+
+        let mut dropped = Vec::new();
+        for (side, conn) in &mut conns[..need_dc] {
+            conn.is_connected = false;
+            dropped.push(*side);
+        }
+
+        for side in dropped {
+            let neighbor = side.step(x, y);
+            self.reorient_tile(neighbor.0, neighbor.1);
+        }
+        self.reorient_tile(x, y);
+    }
+
+    /// Points a directional tile at its wiring. Sorters, flipflops and buttons
+    /// take their input from their oldest connection, while joiners output
+    /// through their newest one. Tiles left without connections keep their
+    /// orientation.
+    fn reorient_tile(&mut self, x: usize, y: usize) {
+        let mut conns: Vec<(usize, Orientation)> = Orientation::BY_PRIORITY
+            .iter()
+            .filter_map(|&side| {
+                let conn = self.get_connection(x, y, side)?;
+                if conn.is_connected {
+                    Some((conn.timestamp, side))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        conns.sort_by_key(|(timestamp, _)| *timestamp);
+
+        let (oldest, newest) = match (conns.first(), conns.last()) {
+            (Some(oldest), Some(newest)) => (oldest.1, newest.1),
+            _ => return,
+        };
+        self.touch_tile(x, y);
+        match self.tiles.get_mut(x, y) {
+            Some(Tile::Sorter { orientation, .. })
+            | Some(Tile::Flipflop { orientation, .. })
+            | Some(Tile::Button { orientation }) => *orientation = oldest,
+            Some(Tile::Joiner { orientation }) => *orientation = newest,
+            _ => (),
+        }
     }
 }
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct Connection {
     /// The tick at which this connection was created
     timestamp: usize,
@@ -295,32 +875,462 @@ impl Connection {
 
 impl Default for Board {
     fn default() -> Board {
-        let tiles = Array2D::new(8, 8);
-        Board {
-            tiles,
-            connections_h: Array2D::new(6, 8),
-            connections_v: Array2D::new(8, 6),
-            connection_counter: 0,
-            width: 8,
-            height: 8,
-        }
+        Board::new(8, 8)
     }
 }
 
-pub struct Signal {
-    direction: Orientation,
-}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use Orientation::*;
 
-pub struct Game {
-    board: Board,
-    signals: Vec<Signal>,
-}
+    #[test]
+    fn priority_order() {
+        assert_eq!(Orientation::BY_PRIORITY, [South, East, West, North]);
+        for (rank, side) in Orientation::BY_PRIORITY.iter().enumerate() {
+            assert_eq!(side.priority() as usize, rank);
+        }
+    }
+
+    /// Every order in which a subset of the four sides can get connected.
+    fn connection_orders() -> Vec<Vec<Orientation>> {
+        let mut orders = vec![vec![]];
+        let mut frontier: Vec<Vec<Orientation>> = vec![vec![]];
+        for _ in 0..4 {
+            frontier = frontier
+                .iter()
+                .flat_map(|order| {
+                    [North, East, South, West]
+                        .iter()
+                        .filter(move |side| !order.contains(side))
+                        .map(move |&side| {
+                            let mut order = order.clone();
+                            order.push(side);
+                            order
+                        })
+                })
+                .collect();
+            orders.extend(frontier.iter().cloned());
+        }
+        orders
+    }
+
+    fn orientation(tile: Option<&Tile>) -> Orientation {
+        match tile {
+            Some(Tile::Sorter { orientation, .. })
+            | Some(Tile::Flipflop { orientation, .. })
+            | Some(Tile::Button { orientation })
+            | Some(Tile::Joiner { orientation }) => *orientation,
+            tile => panic!("{:?} has no orientation", tile),
+        }
+    }
+
+    #[test]
+    fn reorientation() {
+        let tiles = [
+            Tile::Sorter {
+                orientation: North,
+                reversed: false,
+            },
+            Tile::Flipflop {
+                orientation: North,
+                reversed: true,
+            },
+            Tile::Button { orientation: North },
+            Tile::Joiner { orientation: North },
+        ];
+        for &tile in &tiles {
+            for order in connection_orders() {
+                let mut board = Board::default();
+                board.set_tile(1, 1, tile);
+                for &side in &[North, East, South, West] {
+                    let (x, y) = side.step(1, 1);
+                    board.set_tile(x, y, Tile::Wire { slow: false });
+                }
+
+                // The sides that should stay connected, oldest first.
+                let mut kept = Vec::new();
+                for &side in &order {
+                    board.connect(1, 1, side);
+                    kept.push(side);
+                    if kept.len() > tile.max_connections() {
+                        kept.remove(0);
+                    }
+                }
+
+                let expected = match tile {
+                    Tile::Joiner { .. } => kept.last(),
+                    _ => kept.first(),
+                };
+                let expected = expected.copied().unwrap_or(North);
+                assert_eq!(
+                    orientation(board.get_tile(1, 1)),
+                    expected,
+                    "{:?} wired {:?}",
+                    tile,
+                    order
+                );
+                for &side in &[North, East, South, West] {
+                    assert_eq!(
+                        board.is_connected(1, 1, side),
+                        kept.contains(&side),
+                        "{:?} wired {:?}",
+                        tile,
+                        order
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reorientation_after_neighbor_drops_connection() {
+        let mut board = Board::default();
+        board.set_tile(
+            1,
+            1,
+            Tile::Sorter {
+                orientation: North,
+                reversed: false,
+            },
+        );
+        for &(x, y) in &[(0, 0), (0, 1), (0, 2), (1, 2), (2, 1)] {
+            board.set_tile(x, y, Tile::Wire { slow: false });
+        }
+        board.connect(1, 1, West);
+        board.connect(1, 1, South);
+        board.connect(1, 1, East);
+        assert_eq!(orientation(board.get_tile(1, 1)), West);
+
+        // The wire west of the sorter can only keep two connections: wiring
+        // it north and south drops the sorter's input.
+        board.connect(0, 1, North);
+        board.connect(0, 1, South);
+        assert!(!board.is_connected(1, 1, West));
+        assert_eq!(orientation(board.get_tile(1, 1)), South);
+    }
+
+    /// A sorter at (1, 1) wired west, south and east, in that order.
+    fn wired_sorter() -> Board {
+        let mut board = Board::default();
+        board.set_tile(
+            1,
+            1,
+            Tile::Sorter {
+                orientation: North,
+                reversed: false,
+            },
+        );
+        for &(x, y) in &[(0, 1), (1, 2), (2, 1)] {
+            board.set_tile(x, y, Tile::Wire { slow: false });
+        }
+        board.connect(1, 1, West);
+        board.connect(1, 1, South);
+        board.connect(1, 1, East);
+        board
+    }
+
+    #[test]
+    fn disconnect() {
+        let mut board = wired_sorter();
+        board.disconnect(1, 1, West);
+        assert_eq!(board.get_connections(1, 1), [false, true, true, false]);
+        assert_eq!(board.get_connections(0, 1), [false, false, false, false]);
+        assert_eq!(orientation(board.get_tile(1, 1)), South);
+
+        // Disconnecting from the other end works the same.
+        board.disconnect(1, 2, North);
+        assert_eq!(board.get_connections(1, 1), [false, true, false, false]);
+        assert_eq!(orientation(board.get_tile(1, 1)), East);
+    }
+
+    #[test]
+    fn clear_tile() {
+        let mut board = wired_sorter();
+        board.clear_tile(1, 1);
+        assert_eq!(board.get_tile(1, 1), Some(&Tile::Empty));
+        assert_eq!(board.get_connections(1, 1), [false; 4]);
+        for &(x, y) in &[(0, 1), (1, 2), (2, 1)] {
+            assert_eq!(board.get_connections(x, y), [false; 4]);
+        }
+    }
+
+    #[test]
+    fn rotate_tile() {
+        let mut board = wired_sorter();
+        assert!(board.rotate_tile(1, 1));
+        assert_eq!(orientation(board.get_tile(1, 1)), North);
+        assert_eq!(board.get_connections(1, 1), [false, true, true, true]);
+        assert!(!board.rotate_tile(1, 2));
+        assert!(!board.rotate_tile(9, 9));
+
+        board.undo();
+        assert_eq!(board.get_tile(1, 1).unwrap().orientation(), Some(West));
+        // Rewiring points the tile back at its connections.
+        board.rotate_tile(1, 1);
+        board.disconnect(1, 1, East);
+        assert_eq!(orientation(board.get_tile(1, 1)), West);
+    }
+
+    #[test]
+    fn set_tile_drops_extra_connections() {
+        let mut board = wired_sorter();
+        board.set_tile(1, 1, Tile::Wire { slow: false });
+        // A wire holds two connections: the oldest one goes.
+        assert_eq!(board.get_connections(1, 1), [false, true, true, false]);
+
+        board.set_tile(1, 1, Tile::Empty);
+        assert_eq!(board.get_connections(1, 1), [false; 4]);
+    }
+
+    #[test]
+    fn new_board_connects_up_to_its_edges() {
+        let mut board = Board::new(3, 2);
+        for y in 0..2 {
+            for x in 0..3 {
+                board.set_tile(x, y, Tile::Bridge);
+            }
+        }
+        board.connect(1, 0, East);
+        board.connect(2, 0, South);
+        board.connect(2, 1, East);
+        board.connect(2, 1, South);
+        assert_eq!(board.get_connections(2, 0), [false, false, true, true]);
+        assert_eq!(board.get_connections(2, 1), [true, false, false, false]);
+    }
+
+    #[test]
+    fn resize() {
+        let mut board = wired_sorter();
+        board.resize(2, 3);
+        assert_eq!((board.width(), board.height()), (2, 3));
+        assert_eq!(board.get_tile(2, 1), None);
+        // The sorter lost its east connection.
+        assert_eq!(board.get_connections(1, 1), [false, false, true, true]);
+        assert_eq!(orientation(board.get_tile(1, 1)), West);
+
+        board.resize(4, 4);
+        assert_eq!(board.get_tile(2, 1), Some(&Tile::Empty));
+        assert_eq!(board.get_tile(1, 2), Some(&Tile::Wire { slow: false }));
+        assert_eq!(board.get_connections(1, 1), [false, false, true, true]);
+        board.set_tile(3, 3, Tile::Bridge);
+        board.set_tile(3, 2, Tile::Bridge);
+        board.connect(3, 3, North);
+        assert!(board.is_connected(3, 2, South));
+    }
+
+    fn wired(sides: &[Orientation]) -> [bool; 4] {
+        [North, East, South, West].map(|side| sides.contains(&side))
+    }
 
-impl Game {
-    pub fn with(board: Board) -> Game {
-        Game {
-            board,
-            signals: Vec::new(),
+    /// Each row is: tile before, side the signal enters from, signal value,
+    /// connected sides, expected reaction, tile after.
+    type TruthTable = [(
+        Tile,
+        Orientation,
+        u32,
+        &'static [Orientation],
+        Reaction,
+        Tile,
+    )];
+
+    fn check(table: &TruthTable) {
+        for (before, from, value, sides, reaction, after) in table {
+            let mut tile = *before;
+            assert_eq!(
+                tile.react(*from, *value, wired(sides)),
+                *reaction,
+                "{:?} entered from {:?} with {} (wired {:?})",
+                before,
+                from,
+                value,
+                sides
+            );
+            assert_eq!(tile, *after, "state of {:?} after reacting", before);
         }
     }
+
+    #[test]
+    fn wire() {
+        const WIRE: Tile = Tile::Wire { slow: false };
+        #[rustfmt::skip]
+        const TABLE: &TruthTable = &[
+            (WIRE, West, 3, &[West, East], Reaction::Emit(East, 3), WIRE),
+            (WIRE, West, 3, &[West, South], Reaction::Emit(South, 3), WIRE),
+            (WIRE, South, 3, &[West, South], Reaction::Emit(West, 3), WIRE),
+            (WIRE, West, 3, &[West], Reaction::Consume, WIRE),
+        ];
+        check(TABLE);
+    }
+
+    #[test]
+    fn delay() {
+        assert_eq!(Tile::Wire { slow: false }.delay(), 0);
+        assert_eq!(Tile::Wire { slow: true }.delay(), SLOW_WIRE_DELAY);
+        assert_eq!(Tile::Bridge.delay(), 0);
+    }
+
+    #[test]
+    fn bridge() {
+        const ALL: &[Orientation] = &[North, East, South, West];
+        #[rustfmt::skip]
+        const TABLE: &TruthTable = &[
+            (Tile::Bridge, West, 1, ALL, Reaction::Emit(East, 1), Tile::Bridge),
+            (Tile::Bridge, North, 1, ALL, Reaction::Emit(South, 1), Tile::Bridge),
+            (Tile::Bridge, North, 1, &[North, East], Reaction::Consume, Tile::Bridge),
+        ];
+        check(TABLE);
+    }
+
+    #[test]
+    fn joiner() {
+        const JOINER: Tile = Tile::Joiner { orientation: East };
+        const ALL: &[Orientation] = &[North, East, South, West];
+        #[rustfmt::skip]
+        const TABLE: &TruthTable = &[
+            (JOINER, West, 4, ALL, Reaction::Emit(East, 4), JOINER),
+            (JOINER, North, 4, ALL, Reaction::Emit(East, 4), JOINER),
+            (JOINER, South, 4, ALL, Reaction::Emit(East, 4), JOINER),
+            (JOINER, East, 4, ALL, Reaction::Consume, JOINER),
+            (JOINER, West, 4, &[West, North], Reaction::Consume, JOINER),
+        ];
+        check(TABLE);
+    }
+
+    #[test]
+    fn cloner() {
+        #[rustfmt::skip]
+        const TABLE: &TruthTable = &[
+            (Tile::Cloner, West, 5, &[West, North, South], Reaction::Split([South, North], 5), Tile::Cloner),
+            (Tile::Cloner, North, 5, &[North, East, West], Reaction::Split([East, West], 5), Tile::Cloner),
+            (Tile::Cloner, West, 5, &[West, East], Reaction::Emit(East, 5), Tile::Cloner),
+            (Tile::Cloner, West, 5, &[West], Reaction::Consume, Tile::Cloner),
+        ];
+        check(TABLE);
+    }
+
+    #[test]
+    fn sorter() {
+        const SORTER: Tile = Tile::Sorter {
+            orientation: West,
+            reversed: false,
+        };
+        const REVERSED: Tile = Tile::Sorter {
+            orientation: West,
+            reversed: true,
+        };
+        const SIDES: &[Orientation] = &[West, North, South];
+        #[rustfmt::skip]
+        const TABLE: &TruthTable = &[
+            (SORTER, West, 0, SIDES, Reaction::Emit(South, 0), SORTER),
+            (SORTER, West, 1, SIDES, Reaction::Emit(North, 1), SORTER),
+            (SORTER, West, 9, SIDES, Reaction::Emit(North, 9), SORTER),
+            (REVERSED, West, 0, SIDES, Reaction::Emit(North, 0), REVERSED),
+            (REVERSED, West, 9, SIDES, Reaction::Emit(South, 9), REVERSED),
+            (SORTER, North, 0, SIDES, Reaction::Consume, SORTER),
+            (SORTER, West, 9, &[West, South], Reaction::Consume, SORTER),
+        ];
+        check(TABLE);
+    }
+
+    #[test]
+    fn deleter() {
+        #[rustfmt::skip]
+        const TABLE: &TruthTable = &[
+            (Tile::Deleter, West, 2, &[West, East], Reaction::Consume, Tile::Deleter),
+            (Tile::Deleter, North, 0, &[North], Reaction::Consume, Tile::Deleter),
+        ];
+        check(TABLE);
+    }
+
+    #[test]
+    fn flipflop() {
+        const FIRST: Tile = Tile::Flipflop {
+            orientation: South,
+            reversed: false,
+        };
+        const SECOND: Tile = Tile::Flipflop {
+            orientation: South,
+            reversed: true,
+        };
+        const SIDES: &[Orientation] = &[South, West, East];
+        // A signal from the input side flips the flipflop even when its output
+        // isn't wired and the signal is consumed. Signals from other sides
+        // leave it alone.
+        #[rustfmt::skip]
+        const TABLE: &TruthTable = &[
+            (FIRST, South, 6, SIDES, Reaction::Emit(East, 6), SECOND),
+            (SECOND, South, 6, SIDES, Reaction::Emit(West, 6), FIRST),
+            (FIRST, West, 6, SIDES, Reaction::Consume, FIRST),
+            (SECOND, West, 6, SIDES, Reaction::Consume, SECOND),
+            (FIRST, South, 6, &[South, East], Reaction::Emit(East, 6), SECOND),
+            (SECOND, South, 6, &[South, East], Reaction::Consume, FIRST),
+            (FIRST, South, 6, &[South], Reaction::Consume, SECOND),
+        ];
+        check(TABLE);
+    }
+
+    #[test]
+    fn incrementer() {
+        const INC: Tile = Tile::Incrementer { reversed: false };
+        const DEC: Tile = Tile::Incrementer { reversed: true };
+        #[rustfmt::skip]
+        const TABLE: &TruthTable = &[
+            (INC, West, 1, &[West, East], Reaction::Emit(East, 2), INC),
+            (INC, West, u32::MAX, &[West, East], Reaction::Emit(East, u32::MAX), INC),
+            (DEC, West, 1, &[West, East], Reaction::Emit(East, 0), DEC),
+            (DEC, West, 0, &[West, North], Reaction::Emit(North, 0), DEC),
+        ];
+        check(TABLE);
+    }
+
+    #[test]
+    fn button() {
+        const BUTTON: Tile = Tile::Button { orientation: North };
+        #[rustfmt::skip]
+        const TABLE: &TruthTable = &[
+            (BUTTON, North, 8, &[North, South], Reaction::Press(South, 8), BUTTON),
+            (BUTTON, North, 8, &[North, East], Reaction::Press(East, 8), BUTTON),
+            (BUTTON, South, 8, &[North, South], Reaction::Consume, BUTTON),
+        ];
+        check(TABLE);
+    }
+
+    #[test]
+    fn lock() {
+        const LOCKED: Tile = Tile::Lock { locked: true };
+        const UNLOCKED: Tile = Tile::Lock { locked: false };
+        #[rustfmt::skip]
+        const TABLE: &TruthTable = &[
+            (LOCKED, West, 3, &[West, East], Reaction::Block, LOCKED),
+            (UNLOCKED, West, 3, &[West, East], Reaction::Emit(East, 3), LOCKED),
+        ];
+        check(TABLE);
+    }
+
+    #[test]
+    fn sub_board() {
+        const SUB_BOARD: Tile = Tile::SubBoard {
+            contents: SubBoardId(0),
+        };
+        #[rustfmt::skip]
+        const TABLE: &TruthTable = &[
+            (SUB_BOARD, West, 3, &[West, East], Reaction::Enter(West, 3), SUB_BOARD),
+        ];
+        check(TABLE);
+    }
+
+    #[test]
+    fn io() {
+        const INPUT: Tile = Tile::Input { data: DataId(0) };
+        const OUTPUT: Tile = Tile::Output {
+            expected_data: DataId(1),
+        };
+        #[rustfmt::skip]
+        const TABLE: &TruthTable = &[
+            (INPUT, East, 1, &[East], Reaction::Consume, INPUT),
+            (OUTPUT, East, 1, &[East], Reaction::Output(1), OUTPUT),
+        ];
+        check(TABLE);
+    }
 }
@@ -1,17 +1,197 @@
+//! Runs a solution against a level without the game, for scripts.
+//!
+//! ```text
+//! super-prime-mover [--max-ticks N] [--seed S] [--seeds N] [--trace] <level> <solution>
+//! ```
+//!
+//! The level is a text file, see [`super_prime_mover::level`]. The solution
+//! is a board, in the text format if its name ends in `.txt`, in the binary
+//! save format otherwise. It is checked against the level, then run on every
+//! test case, stopping at the first failure.
+//!
+//! Levels with a generator also get `--seeds` generated test cases, from
+//! seed `--seed` on. A failing seed can be run on its own with
+//! `--seed S --seeds 1`.
+//!
+//! Exits with 0 if the solution passes, 1 if it fails, breaking the level's
+//! rules included, and 2 if a file couldn't be read.
+
+use std::path::Path;
+use std::process;
 use super_prime_mover::*;
 
-fn main() {
+const DEFAULT_MAX_TICKS: u64 = 100_000;
+
+const DEFAULT_SEEDS: u64 = 200;
+
+const USAGE: &str =
+    "usage: super-prime-mover [--max-ticks N] [--seed S] [--seeds N] [--trace] <level> <solution>";
+
+struct Options {
+    level: String,
+    solution: String,
+    max_ticks: u64,
+    /// The first seed to generate test cases from.
+    seed: u64,
+    /// How many test cases to generate.
+    seeds: u64,
+    trace: bool,
+}
+
+fn number(value: Option<String>, option: &str) -> Result<u64, String> {
+    value
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| format!("{} expects a number", option))
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut files = Vec::new();
+    let mut max_ticks = DEFAULT_MAX_TICKS;
+    let mut seed = 0;
+    let mut seeds = DEFAULT_SEEDS;
+    let mut trace = false;
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--max-ticks" => max_ticks = number(args.next(), &arg)?,
+            "--seed" => seed = number(args.next(), &arg)?,
+            "--seeds" => seeds = number(args.next(), &arg)?,
+            "--trace" => trace = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ => files.push(arg),
+        }
+    }
+    match files.as_slice() {
+        [level, solution] => Ok(Options {
+            level: level.clone(),
+            solution: solution.clone(),
+            max_ticks,
+            seed,
+            seeds,
+            trace,
+        }),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn load_board(path: &str) -> Result<Board, String> {
+    let error = |e: &dyn std::fmt::Display| format!("{}: {}", path, e);
+    if Path::new(path).extension() == Some("txt".as_ref()) {
+        let text = std::fs::read_to_string(path).map_err(|e| error(&e))?;
+        Board::from_text(&text).map_err(|e| error(&e))
+    } else {
+        let data = std::fs::read(path).map_err(|e| error(&e))?;
+        Board::load(&data).map_err(|e| error(&e))
+    }
+}
 
-    let board = Board::default();
-    // TODO: Populate board
+fn load_level(path: &str) -> Result<Level, String> {
+    let error = |e: &dyn std::fmt::Display| format!("{}: {}", path, e);
+    let text = std::fs::read_to_string(path).map_err(|e| error(&e))?;
+    Level::from_text(&text).map_err(|e| error(&e))
+}
 
-    let game = Game::with(board);
+fn run(options: &Options) -> Result<bool, String> {
+    let level = load_level(&options.level)?;
+    let solution = load_board(&options.solution)?;
 
-    loop {
+    let seeds = options.seed..options.seed.saturating_add(options.seeds);
+    let mut traced = None;
+    let on_tick = |case, game: &Game| {
+        if options.trace {
+            if traced != Some(case) {
+                println!("{}", case);
+                traced = Some(case);
+            }
+            print_signals(game);
+        }
+    };
+    match level.run(&solution, seeds, options.max_ticks, on_tick) {
+        Ok(score) => {
+            println!("verdict: pass");
+            println!("{}", score);
+            if let Some(par) = &level.par {
+                let verdict = if score.meets(par) { "met" } else { "missed" };
+                println!(
+                    "par: {} ({} cycles, {} tiles)",
+                    verdict, par.cycles, par.tiles
+                );
+            }
+            Ok(true)
+        }
+        Err(Failure::Invalid(violation)) => {
+            println!("verdict: fail");
+            println!("{}", violation);
+            Ok(false)
+        }
+        Err(Failure::Case { case, verdict }) => {
+            println!("verdict: fail");
+            println!("{}: {}", case, verdict);
+            if let CaseId::Seed(seed) = case {
+                println!("reproduce with --seed {} --seeds 1", seed);
+            }
+            Ok(false)
+        }
+    }
+}
 
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(2);
+        }
+    };
+    match run(&options) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(message) => {
+            eprintln!("error: {}", message);
+            process::exit(2);
+        }
     }
 }
 
-fn print_board(board: &Board) {
-    
-}
\ No newline at end of file
+fn print_signals(game: &Game) {
+    let signals: Vec<String> = game
+        .signals()
+        .iter()
+        .map(|signal| {
+            let (x, y) = signal.position();
+            format!("{} at ({}, {})", signal.value(), x, y)
+        })
+        .collect();
+    println!("tick {}: {}", game.tick(), signals.join(", "));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn arguments() {
+        let options = parse_args(args(&["--max-ticks", "50", "a.txt", "b"])).unwrap();
+        assert_eq!(
+            (options.level.as_str(), options.solution.as_str()),
+            ("a.txt", "b")
+        );
+        assert_eq!(options.max_ticks, 50);
+        assert_eq!((options.seed, options.seeds), (0, DEFAULT_SEEDS));
+        assert!(!options.trace);
+
+        let options = parse_args(args(&["a.txt", "--seed", "7", "--seeds", "1", "b"])).unwrap();
+        assert_eq!((options.seed, options.seeds), (7, 1));
+
+        assert!(parse_args(args(&["a.txt"])).is_err());
+        assert!(parse_args(args(&["--max-ticks", "a.txt", "b"])).is_err());
+        assert!(parse_args(args(&["--fast", "a.txt", "b"])).is_err());
+    }
+}
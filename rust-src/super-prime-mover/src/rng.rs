@@ -0,0 +1,60 @@
+/// A small seedable random number generator (SplitMix64), so that generated
+/// test cases can be reproduced from their seed on any platform.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A value between `min` and `max`, both included.
+    pub fn range(&mut self, min: u32, max: u32) -> u32 {
+        assert!(min <= max, "empty range {}..={}", min, max);
+        let span = u64::from(max - min) + 1;
+        // Multiplying instead of taking the remainder keeps the bias
+        // negligible for spans this small.
+        min + ((u128::from(self.next_u64()) * u128::from(span)) >> 64) as u32
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_values() {
+        let values = |seed| {
+            let mut rng = Rng::new(seed);
+            (0..8).map(|_| rng.next_u64()).collect::<Vec<_>>()
+        };
+        assert_eq!(values(7), values(7));
+        assert_ne!(values(7), values(8));
+        // SplitMix64's reference output for seed 0.
+        assert_eq!(values(0)[0], 0xe220_a839_7b1d_cdaf);
+    }
+
+    #[test]
+    fn range_stays_in_bounds() {
+        let mut rng = Rng::new(1);
+        let mut seen = [false; 4];
+        for _ in 0..1000 {
+            let value = rng.range(10, 13);
+            assert!((10..=13).contains(&value));
+            seen[(value - 10) as usize] = true;
+        }
+        assert_eq!(seen, [true; 4]);
+        assert_eq!(rng.range(5, 5), 5);
+        rng.range(0, u32::MAX);
+    }
+}
@@ -0,0 +1,513 @@
+//! Binary save format for boards, and solutions built on them.
+//!
+//! A save starts with the [`MAGIC`] bytes and a little-endian `u16` format
+//! version, followed by the board. All integers are little-endian.
+//!
+//! ```text
+//! board      := width:u32 height:u32 counter:u64 store tile*
+//!               connection{(width - 1) * height} connection{width * (height - 1)}
+//! store      := count:u32 (board icon)* count:u32 (len:u32 value:u32*)*
+//! tile       := tag:u8 payload
+//! connection := connected:u8 timestamp:u64
+//! icon       := tag:u8 (len:u32 png:u8*)?
+//! ```
+//!
+//! Horizontal connections come first, each linking a tile to its east
+//! neighbor, then vertical ones, linking a tile to its south neighbor. Both
+//! are in row-major order, like tiles.
+//!
+//! Sub-boards are stored inside the board holding them, at most
+//! [`MAX_DEPTH`] levels deep.
+//!
+//! Each format version has its own reader, which upgrades the boards it
+//! reads to the current layout. When the format changes, the reader for the
+//! old version stays, so older saves keep loading.
+//!
+//! A solution is just a saved [`Board`]: the level it solves, and its name,
+//! aren't part of the save, see [`crate::level`].
+
+use crate::{Board, BoardIcon, DataId, Orientation, SubBoardId, Tile};
+use std::fmt;
+
+/// The bytes every save starts with.
+pub const MAGIC: &[u8; 4] = b"SPMB";
+
+/// The version of the format written by [`Board::save`].
+pub const FORMAT_VERSION: u16 = 1;
+
+/// How many levels deep sub-boards can be nested in a save.
+pub const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    /// The data doesn't start with [`MAGIC`].
+    BadMagic,
+    /// The save was written in a format version this game can't read.
+    UnsupportedVersion(u16),
+    /// The data ended in the middle of the save.
+    UnexpectedEof,
+    InvalidTile(u8),
+    InvalidOrientation(u8),
+    InvalidIcon(u8),
+    /// A tile refers to a sub-board or data that isn't in the save.
+    InvalidReference,
+    /// There is data left after the board.
+    TrailingData,
+    /// Sub-boards are nested more than [`MAX_DEPTH`] levels deep.
+    TooDeep,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::BadMagic => write!(f, "not a board save"),
+            LoadError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save format version {}", version)
+            }
+            LoadError::UnexpectedEof => write!(f, "save is truncated"),
+            LoadError::InvalidTile(tag) => write!(f, "invalid tile {}", tag),
+            LoadError::InvalidOrientation(tag) => write!(f, "invalid orientation {}", tag),
+            LoadError::InvalidIcon(tag) => write!(f, "invalid board icon {}", tag),
+            LoadError::InvalidReference => write!(f, "tile refers to missing data"),
+            LoadError::TrailingData => write!(f, "unexpected data after the board"),
+            LoadError::TooDeep => write!(f, "sub-boards are nested too deeply"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl Board {
+    /// Serializes the board, sub-boards included. Edit history isn't saved.
+    pub fn save(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        write_board(&mut out, self);
+        out
+    }
+
+    /// Deserializes a board saved by [`Board::save`].
+    pub fn load(data: &[u8]) -> Result<Board, LoadError> {
+        let mut reader = Reader { data };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(LoadError::BadMagic);
+        }
+        let board = match reader.u16()? {
+            1 => reader.board_v1(0)?,
+            version => return Err(LoadError::UnsupportedVersion(version)),
+        };
+        if !reader.data.is_empty() {
+            return Err(LoadError::TrailingData);
+        }
+        Ok(board)
+    }
+}
+
+fn write_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_board(out: &mut Vec<u8>, board: &Board) {
+    write_u32(out, board.width as u32);
+    write_u32(out, board.height as u32);
+    out.extend_from_slice(&(board.connection_counter as u64).to_le_bytes());
+
+    write_u32(out, board.store.sub_boards.len() as u32);
+    for sub_board in &board.store.sub_boards {
+        write_board(out, &sub_board.contents);
+        match &sub_board.icon {
+            BoardIcon::Green => out.push(0),
+            BoardIcon::Red => out.push(1),
+            BoardIcon::Blue => out.push(2),
+            BoardIcon::Other { png_data } => {
+                out.push(3);
+                write_u32(out, png_data.len() as u32);
+                out.extend_from_slice(png_data);
+            }
+        }
+    }
+    write_u32(out, board.store.data.len() as u32);
+    for data in &board.store.data {
+        write_u32(out, data.len() as u32);
+        for &value in data.iter() {
+            write_u32(out, value);
+        }
+    }
+
+    for (_, _, tile) in &board.tiles {
+        write_tile(out, tile);
+    }
+    for connections in &[&board.connections_h, &board.connections_v] {
+        for (_, _, conn) in *connections {
+            out.push(conn.is_connected as u8);
+            out.extend_from_slice(&(conn.timestamp as u64).to_le_bytes());
+        }
+    }
+}
+
+fn write_tile(out: &mut Vec<u8>, tile: &Tile) {
+    match *tile {
+        Tile::Empty => out.push(0),
+        Tile::Unusable { broken } => out.extend_from_slice(&[1, broken as u8]),
+        Tile::Wire { slow } => out.extend_from_slice(&[2, slow as u8]),
+        Tile::Bridge => out.push(3),
+        Tile::Joiner { orientation } => out.extend_from_slice(&[4, orientation as u8]),
+        Tile::Cloner => out.push(5),
+        Tile::Sorter {
+            orientation,
+            reversed,
+        } => out.extend_from_slice(&[6, orientation as u8, reversed as u8]),
+        Tile::Deleter => out.push(7),
+        Tile::Flipflop {
+            orientation,
+            reversed,
+        } => out.extend_from_slice(&[8, orientation as u8, reversed as u8]),
+        Tile::Incrementer { reversed } => out.extend_from_slice(&[9, reversed as u8]),
+        Tile::Button { orientation } => out.extend_from_slice(&[10, orientation as u8]),
+        Tile::Lock { locked } => out.extend_from_slice(&[11, locked as u8]),
+        Tile::SubBoard { contents } => {
+            out.push(12);
+            write_u32(out, contents.0);
+        }
+        Tile::Input { data } => {
+            out.push(13);
+            write_u32(out, data.0);
+        }
+        Tile::Output { expected_data } => {
+            out.push(14);
+            write_u32(out, expected_data.0);
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], LoadError> {
+        if self.data.len() < len {
+            return Err(LoadError::UnexpectedEof);
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, LoadError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, LoadError> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn u16(&mut self) -> Result<u16, LoadError> {
+        let mut bytes = [0; 2];
+        bytes.copy_from_slice(self.take(2)?);
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    fn u32(&mut self) -> Result<u32, LoadError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64, LoadError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    /// Reads a count of items taking at least `min_size` bytes each, making
+    /// sure the data is long enough to hold them before anything gets
+    /// allocated.
+    fn count(&mut self, min_size: usize) -> Result<usize, LoadError> {
+        let count = self.u32()? as usize;
+        if count.saturating_mul(min_size) > self.data.len() {
+            return Err(LoadError::UnexpectedEof);
+        }
+        Ok(count)
+    }
+
+    fn orientation(&mut self) -> Result<Orientation, LoadError> {
+        match self.u8()? {
+            0 => Ok(Orientation::South),
+            1 => Ok(Orientation::West),
+            2 => Ok(Orientation::East),
+            3 => Ok(Orientation::North),
+            tag => Err(LoadError::InvalidOrientation(tag)),
+        }
+    }
+
+    /// Reads a version 1 board, nested `depth` levels deep in the saved one.
+    fn board_v1(&mut self, depth: usize) -> Result<Board, LoadError> {
+        if depth > MAX_DEPTH {
+            return Err(LoadError::TooDeep);
+        }
+        let width = self.u32()? as usize;
+        let height = self.u32()? as usize;
+        if width.saturating_mul(height) > self.data.len() {
+            return Err(LoadError::UnexpectedEof);
+        }
+        let mut board = Board::new(width, height);
+        board.connection_counter = self.u64()? as usize;
+
+        for _ in 0..self.count(9)? {
+            let contents = self.board_v1(depth + 1)?;
+            let icon = match self.u8()? {
+                0 => BoardIcon::Green,
+                1 => BoardIcon::Red,
+                2 => BoardIcon::Blue,
+                3 => {
+                    let len = self.count(1)?;
+                    BoardIcon::Other {
+                        png_data: self.take(len)?.to_vec(),
+                    }
+                }
+                tag => return Err(LoadError::InvalidIcon(tag)),
+            };
+            board.store.add_sub_board(contents, icon);
+        }
+        for _ in 0..self.count(4)? {
+            let len = self.count(4)?;
+            let data = (0..len).map(|_| self.u32()).collect::<Result<_, _>>()?;
+            board.store.add_data(data);
+        }
+
+        for y in 0..height {
+            for x in 0..width {
+                let tile = self.tile_v1(&board)?;
+                *board.tiles.get_mut(x, y).unwrap() = tile;
+            }
+        }
+
+        for connections in &mut [&mut board.connections_h, &mut board.connections_v] {
+            for (_, _, conn) in &mut **connections {
+                conn.is_connected = self.bool()?;
+                conn.timestamp = self.u64()? as usize;
+            }
+        }
+
+        Ok(board)
+    }
+
+    fn tile_v1(&mut self, board: &Board) -> Result<Tile, LoadError> {
+        let tile = match self.u8()? {
+            0 => Tile::Empty,
+            1 => Tile::Unusable {
+                broken: self.bool()?,
+            },
+            2 => Tile::Wire { slow: self.bool()? },
+            3 => Tile::Bridge,
+            4 => Tile::Joiner {
+                orientation: self.orientation()?,
+            },
+            5 => Tile::Cloner,
+            6 => Tile::Sorter {
+                orientation: self.orientation()?,
+                reversed: self.bool()?,
+            },
+            7 => Tile::Deleter,
+            8 => Tile::Flipflop {
+                orientation: self.orientation()?,
+                reversed: self.bool()?,
+            },
+            9 => Tile::Incrementer {
+                reversed: self.bool()?,
+            },
+            10 => Tile::Button {
+                orientation: self.orientation()?,
+            },
+            11 => Tile::Lock {
+                locked: self.bool()?,
+            },
+            12 => {
+                let id = self.u32()?;
+                if id as usize >= board.store.sub_boards.len() {
+                    return Err(LoadError::InvalidReference);
+                }
+                Tile::SubBoard {
+                    contents: SubBoardId(id),
+                }
+            }
+            tag @ 13 | tag @ 14 => {
+                let id = self.u32()?;
+                if id as usize >= board.store.data.len() {
+                    return Err(LoadError::InvalidReference);
+                }
+                if tag == 13 {
+                    Tile::Input { data: DataId(id) }
+                } else {
+                    Tile::Output {
+                        expected_data: DataId(id),
+                    }
+                }
+            }
+            tag => return Err(LoadError::InvalidTile(tag)),
+        };
+        Ok(tile)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A board using every kind of tile, with a nested sub-board.
+    fn sample_board() -> Board {
+        let mut inner = Board::new(3, 2);
+        let data = inner.store_mut().add_data(vec![]);
+        inner.set_tile(0, 0, Tile::Input { data });
+        inner.set_tile(1, 0, Tile::Incrementer { reversed: true });
+        inner.set_tile(
+            2,
+            0,
+            Tile::Output {
+                expected_data: data,
+            },
+        );
+        inner.connect(0, 0, Orientation::East);
+        inner.connect(1, 0, Orientation::East);
+
+        let mut board = Board::new(5, 4);
+        let icon = BoardIcon::Other {
+            png_data: vec![0x89, b'P', b'N', b'G'],
+        };
+        let contents = board.store_mut().add_sub_board(inner, icon);
+        let data = board.store_mut().add_data(vec![1, 2, 3]);
+        let expected_data = board.store_mut().add_data(vec![u32::MAX]);
+        let tiles = [
+            Tile::Empty,
+            Tile::Unusable { broken: true },
+            Tile::Wire { slow: true },
+            Tile::Bridge,
+            Tile::Joiner {
+                orientation: Orientation::West,
+            },
+            Tile::Cloner,
+            Tile::Sorter {
+                orientation: Orientation::North,
+                reversed: true,
+            },
+            Tile::Deleter,
+            Tile::Flipflop {
+                orientation: Orientation::East,
+                reversed: false,
+            },
+            Tile::Incrementer { reversed: false },
+            Tile::Button {
+                orientation: Orientation::South,
+            },
+            Tile::Lock { locked: true },
+            Tile::SubBoard { contents },
+            Tile::Input { data },
+            Tile::Output { expected_data },
+        ];
+        for (i, &tile) in tiles.iter().enumerate() {
+            board.set_tile(i % 5, i / 5, tile);
+        }
+        board.connect(2, 0, Orientation::East);
+        board.connect(3, 0, Orientation::South);
+        board.connect(3, 1, Orientation::West);
+        board
+    }
+
+    #[test]
+    fn round_trip() {
+        let board = sample_board();
+        let loaded = Board::load(&board.save()).unwrap();
+        assert_eq!(loaded, board);
+        assert_eq!(loaded.connection_counter, board.connection_counter);
+        assert_eq!(loaded.save(), board.save());
+    }
+
+    #[test]
+    fn round_trip_keeps_timestamps() {
+        let mut board = sample_board();
+        let mut loaded = Board::load(&board.save()).unwrap();
+        // The bridge at (3, 0) holds four connections: the oldest goes first
+        // in both boards.
+        for board in &mut [&mut board, &mut loaded] {
+            board.set_tile(4, 0, Tile::Wire { slow: false });
+            board.connect(3, 0, Orientation::East);
+            board.connect(3, 0, Orientation::North);
+        }
+        assert_eq!(loaded, board);
+    }
+
+    /// A version 1 save of `I0-% -O1`, with 7 as both input and expected
+    /// output. Whatever the current version, it has to keep loading.
+    #[rustfmt::skip]
+    const V1_SAVE: &[u8] = &[
+        b'S', b'P', b'M', b'B', 1, 0,
+        // 3x1, with 2 connections made so far.
+        3, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0,
+        // No sub-boards, then two data.
+        0, 0, 0, 0, 2, 0, 0, 0,
+        1, 0, 0, 0, 7, 0, 0, 0,
+        1, 0, 0, 0, 7, 0, 0, 0,
+        // Input 0, a slow wire and output 1.
+        13, 0, 0, 0, 0, 2, 1, 14, 1, 0, 0, 0,
+        // Both horizontal connections, with their timestamps.
+        1, 0, 0, 0, 0, 0, 0, 0, 0,
+        1, 1, 0, 0, 0, 0, 0, 0, 0,
+    ];
+
+    #[test]
+    fn loads_version_1() {
+        let board = Board::load(V1_SAVE).unwrap();
+        let expected = Board::from_text("I0-% -O1\n\ndata 0: 7\ndata 1: 7").unwrap();
+        assert_eq!(board, expected);
+        assert_eq!(board.connection_counter, 2);
+    }
+
+    #[test]
+    fn deep_nesting() {
+        // Empty boards, each holding the next one as its only sub-board.
+        let nested = |depth: usize| {
+            let mut out = MAGIC.to_vec();
+            out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+            for _ in 0..depth {
+                // Width, height and connection counter.
+                out.extend_from_slice(&[0; 16]);
+                write_u32(&mut out, 1);
+            }
+            out.extend_from_slice(&[0; 24]);
+            for _ in 0..depth {
+                out.push(0);
+                write_u32(&mut out, 0);
+            }
+            out
+        };
+        assert!(Board::load(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(Board::load(&nested(MAX_DEPTH + 1)), Err(LoadError::TooDeep));
+        // Too deep to read recursively without running out of stack.
+        assert_eq!(Board::load(&nested(1_000_000)), Err(LoadError::TooDeep));
+    }
+
+    #[test]
+    fn errors() {
+        let save = sample_board().save();
+        assert_eq!(Board::load(b"nope"), Err(LoadError::BadMagic));
+
+        for &version in &[0, FORMAT_VERSION + 1] {
+            let mut unsupported = save.clone();
+            unsupported[4..6].copy_from_slice(&version.to_le_bytes());
+            assert_eq!(
+                Board::load(&unsupported),
+                Err(LoadError::UnsupportedVersion(version))
+            );
+        }
+
+        for len in 0..save.len() {
+            assert!(Board::load(&save[..len]).is_err());
+        }
+
+        let mut trailing = save;
+        trailing.push(0);
+        assert_eq!(Board::load(&trailing), Err(LoadError::TrailingData));
+    }
+}
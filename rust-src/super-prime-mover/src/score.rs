@@ -0,0 +1,179 @@
+use crate::{Board, Level, Par, Tile, TileKind};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// How well a solution did on a level, see [`Level::run`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Score {
+    /// The ticks each test case took, on average.
+    pub mean_cycles: f64,
+    /// The ticks the slowest test case took.
+    pub worst_cycles: u64,
+    /// The tiles the solution placed, by kind. Tiles inside sub-boards count
+    /// too, except for their ports. The level's fixed tiles don't.
+    pub tiles: BTreeMap<TileKind, usize>,
+    /// The area of the smallest rectangle holding every tile the solution
+    /// placed on the level's board.
+    pub area: usize,
+}
+
+impl Score {
+    pub(crate) fn new(level: &Level, solution: &Board, cycles: &[u64]) -> Score {
+        let mut tiles = BTreeMap::new();
+        let mut bounds: Option<(usize, usize, usize, usize)> = None;
+        for (x, y, tile) in &solution.tiles {
+            if *tile == Tile::Empty || level.is_fixed(x, y) {
+                continue;
+            }
+            count_tiles(solution, tile, &mut tiles);
+            bounds = Some(match bounds {
+                None => (x, y, x, y),
+                Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+            });
+        }
+
+        let total: u64 = cycles.iter().sum();
+        Score {
+            mean_cycles: if cycles.is_empty() {
+                0.0
+            } else {
+                total as f64 / cycles.len() as f64
+            },
+            worst_cycles: cycles.iter().copied().max().unwrap_or(0),
+            tiles,
+            area: bounds.map_or(0, |(x0, y0, x1, y1)| (x1 - x0 + 1) * (y1 - y0 + 1)),
+        }
+    }
+
+    /// The number of tiles placed, of any kind.
+    pub fn tile_count(&self) -> usize {
+        self.tiles.values().sum()
+    }
+
+    /// Whether the solution is as fast and as small as the level's par.
+    pub fn meets(&self, par: &Par) -> bool {
+        self.worst_cycles <= par.cycles && self.tile_count() <= par.tiles
+    }
+}
+
+/// Counts `tile`, and whatever is inside it if it's a sub-board. The inputs
+/// and outputs inside are its ports, which are free, as in
+/// [`Level::validate`].
+fn count_tiles(board: &Board, tile: &Tile, tiles: &mut BTreeMap<TileKind, usize>) {
+    if *tile == Tile::Empty {
+        return;
+    }
+    *tiles.entry(tile.kind()).or_insert(0) += 1;
+    if let Tile::SubBoard { contents } = tile {
+        let contents = match board.store().sub_board(*contents) {
+            Some(sub_board) => &sub_board.contents,
+            None => return,
+        };
+        for (_, _, tile) in &contents.tiles {
+            if let Tile::Input { .. } | Tile::Output { .. } = tile {
+                continue;
+            }
+            count_tiles(contents, tile, tiles);
+        }
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "cycles: {:.1} mean, {} worst",
+            self.mean_cycles, self.worst_cycles
+        )?;
+        write!(f, "tiles: {}", self.tile_count())?;
+        let kinds: Vec<String> = self
+            .tiles
+            .iter()
+            .map(|(kind, count)| format!("{} {}", count, kind.name()))
+            .collect();
+        if !kinds.is_empty() {
+            write!(f, " ({})", kinds.join(", "))?;
+        }
+        write!(f, "\narea: {}", self.area)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::BoardIcon;
+
+    const LEVEL: &str = "\
+name: Filter
+allow: wire sorter deleter sub-board incrementer
+test: 0 3 0 5 -> 3 5
+test: 0 0 0 0 0 0 0 0 0 0 0 7 -> 7
+
+I0 .  .  .  O0
+
+.  .  .  .  .
+
+data 0:
+";
+
+    const SOLUTION: &str = "\
+. -* -S<-* -.
+      |
+.  .  D  .  .
+";
+
+    #[test]
+    fn scores_passing_run() {
+        let level = Level::from_text(LEVEL).unwrap();
+        let solution = Board::from_text(SOLUTION).unwrap();
+        let score = level.run(&solution, 0..0, 1000, |_, _| {}).unwrap();
+        assert_eq!(score.worst_cycles, 37);
+        assert_eq!(score.mean_cycles, (14.0 + 37.0) / 2.0);
+        let tiles: Vec<_> = score.tiles.iter().map(|(&k, &n)| (k, n)).collect();
+        assert_eq!(
+            tiles,
+            [
+                (TileKind::Wire, 2),
+                (TileKind::Sorter, 1),
+                (TileKind::Deleter, 1)
+            ]
+        );
+        assert_eq!(score.tile_count(), 4);
+        // From (1, 0) to (3, 1).
+        assert_eq!(score.area, 6);
+        assert_eq!(
+            score.to_string(),
+            "cycles: 25.5 mean, 37 worst\n\
+             tiles: 4 (2 wire, 1 sorter, 1 deleter)\n\
+             area: 6"
+        );
+        assert!(score.meets(&Par {
+            cycles: 37,
+            tiles: 4
+        }));
+        assert!(!score.meets(&Par {
+            cycles: 36,
+            tiles: 4
+        }));
+    }
+
+    #[test]
+    fn counts_sub_board_contents() {
+        let level = Level::from_text(LEVEL).unwrap();
+        let mut solution = Board::from_text(SOLUTION).unwrap();
+        let inner = Board::from_text("I0-* -+1-O0\n\ndata 0:").unwrap();
+        let id = solution.store_mut().add_sub_board(inner, BoardIcon::Green);
+        solution.set_tile(4, 1, Tile::SubBoard { contents: id });
+
+        let score = Score::new(&level, &solution, &[]);
+        assert_eq!(score.tiles[&TileKind::Wire], 3);
+        assert_eq!(score.tiles[&TileKind::SubBoard], 1);
+        assert_eq!(score.tiles[&TileKind::Incrementer], 1);
+        // The sub-board's ports are free.
+        assert!(!score.tiles.contains_key(&TileKind::Input));
+        assert!(!score.tiles.contains_key(&TileKind::Output));
+        assert_eq!(score.tile_count(), 7);
+        assert_eq!(score.area, 8);
+        assert_eq!(score.mean_cycles, 0.0);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{Board, BoardIcon};
+use std::sync::Arc;
+
+/// Refers to a sub-board kept in a [`BoardStore`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubBoardId(pub(crate) u32);
+
+/// Refers to the data of an Input or Output tile kept in a [`BoardStore`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DataId(pub(crate) u32);
+
+/// A board nested in another one through a [`crate::Tile::SubBoard`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubBoard {
+    pub contents: Board,
+    pub icon: BoardIcon,
+}
+
+/// Holds the heavy payloads of a board's tiles, so that [`crate::Tile`] can
+/// stay `Copy`.
+///
+/// Entries are shared between clones of a board, and are never removed: the
+/// ids handed out stay valid for as long as the board lives, which undoing
+/// edits relies on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BoardStore {
+    pub(crate) sub_boards: Vec<Arc<SubBoard>>,
+    pub(crate) data: Vec<Arc<[u32]>>,
+}
+
+impl BoardStore {
+    pub fn add_sub_board(&mut self, contents: Board, icon: BoardIcon) -> SubBoardId {
+        self.sub_boards.push(Arc::new(SubBoard { contents, icon }));
+        SubBoardId(self.sub_boards.len() as u32 - 1)
+    }
+
+    /// The sub-board `id` refers to, or `None` if it comes from another
+    /// store.
+    pub fn sub_board(&self, id: SubBoardId) -> Option<&SubBoard> {
+        self.sub_boards.get(id.0 as usize).map(Arc::as_ref)
+    }
+
+    /// Like [`BoardStore::add_sub_board`], but hands out the id of an equal
+    /// sub-board if there is one already. Tiles sharing it see each other's
+    /// edits, so only share sub-boards that don't get edited.
+    pub fn share_sub_board(&mut self, contents: Board, icon: BoardIcon) -> SubBoardId {
+        let sub_board = SubBoard { contents, icon };
+        match self.sub_boards.iter().position(|s| **s == sub_board) {
+            Some(id) => SubBoardId(id as u32),
+            None => {
+                self.sub_boards.push(Arc::new(sub_board));
+                SubBoardId(self.sub_boards.len() as u32 - 1)
+            }
+        }
+    }
+
+    /// Gives mutable access to a sub-board. If clones of the board still
+    /// share it, it is copied first.
+    pub fn sub_board_mut(&mut self, id: SubBoardId) -> Option<&mut SubBoard> {
+        self.sub_boards.get_mut(id.0 as usize).map(Arc::make_mut)
+    }
+
+    pub fn add_data(&mut self, data: Vec<u32>) -> DataId {
+        self.data.push(data.into());
+        DataId(self.data.len() as u32 - 1)
+    }
+
+    /// Like [`BoardStore::add_data`], but hands out the id of equal data if
+    /// there is some already.
+    pub fn share_data(&mut self, data: &[u32]) -> DataId {
+        match self.data.iter().position(|d| **d == *data) {
+            Some(id) => DataId(id as u32),
+            None => self.add_data(data.to_vec()),
+        }
+    }
+
+    /// The data `id` refers to, or `None` if it comes from another store.
+    pub fn data(&self, id: DataId) -> Option<&[u32]> {
+        self.data.get(id.0 as usize).map(Arc::as_ref)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Tile;
+
+    #[test]
+    fn clones_share_sub_boards() {
+        let mut board = Board::default();
+        let id = board
+            .store_mut()
+            .add_sub_board(Board::default(), BoardIcon::Red);
+        let mut copy = board.clone();
+        assert!(Arc::ptr_eq(
+            &board.store().sub_boards[0],
+            &copy.store().sub_boards[0]
+        ));
+
+        copy.store_mut()
+            .sub_board_mut(id)
+            .unwrap()
+            .contents
+            .set_tile(0, 0, Tile::Deleter);
+        assert_eq!(
+            board.store().sub_board(id).unwrap().contents.get_tile(0, 0),
+            Some(&Tile::Empty)
+        );
+        assert_eq!(
+            copy.store().sub_board(id).unwrap().contents.get_tile(0, 0),
+            Some(&Tile::Deleter)
+        );
+    }
+
+    #[test]
+    fn sharing() {
+        let mut store = BoardStore::default();
+        let data = store.share_data(&[1, 2]);
+        assert_eq!(store.share_data(&[1, 2]), data);
+        assert_ne!(store.share_data(&[]), data);
+        assert_ne!(store.add_data(vec![1, 2]), data);
+
+        let sub_board = store.share_sub_board(Board::default(), BoardIcon::Red);
+        assert_eq!(
+            store.share_sub_board(Board::default(), BoardIcon::Red),
+            sub_board
+        );
+        assert_ne!(
+            store.share_sub_board(Board::default(), BoardIcon::Blue),
+            sub_board
+        );
+        assert_eq!((store.sub_boards.len(), store.data.len()), (2, 3));
+    }
+
+    #[test]
+    fn foreign_ids() {
+        let mut other = BoardStore::default();
+        let sub_board = other.add_sub_board(Board::default(), BoardIcon::Red);
+        let data = other.add_data(vec![1]);
+        let mut store = BoardStore::default();
+        assert_eq!(store.sub_board(sub_board), None);
+        assert!(store.sub_board_mut(sub_board).is_none());
+        assert_eq!(store.data(data), None);
+    }
+}
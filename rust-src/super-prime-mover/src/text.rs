@@ -0,0 +1,629 @@
+//! A plain text format for boards, meant to be read and written by people:
+//! tests, bug reports and the terminal runner draw boards with it.
+//!
+//! Every tile is a two character cell, with a connection character between
+//! neighbouring cells: `-` links a tile to its east neighbour, and a `|`
+//! under the first character of a cell links it to its south neighbour.
+//!
+//! ```text
+//! I0-* -* -O1
+//!       |
+//! .  .  *  .
+//!
+//! data 0: 1 2 3
+//! data 1: 1 2 3
+//! ```
+//!
+//! | Cell | Tile |
+//! |------|------|
+//! | `. ` | Empty |
+//! | `x ` / `X ` | Unusable, `X` when broken |
+//! | `* ` / `% ` | Wire, `%` when slow |
+//! | `# ` | Bridge |
+//! | `J>` | Joiner, the arrow pointing at its output |
+//! | `C ` | Cloner |
+//! | `S>` / `s>` | Sorter, the arrow pointing from its input, `s` when reversed |
+//! | `D ` | Deleter |
+//! | `F>` / `f>` | Flipflop, likewise |
+//! | `+1` / `-1` | Incrementer, `-1` when reversed |
+//! | `B>` | Button, likewise |
+//! | `L ` / `l ` | Lock, `L` when locked |
+//! | `@0` | SubBoard |
+//! | `I0` / `O0` | Input and Output |
+//!
+//! Arrows are `^`, `>`, `v` and `<`. Sub-boards and data are numbered in
+//! base 36, which fits 36 of each per board (boards with more can't be
+//! drawn), and described after the grid, after a blank line. A sub-board's
+//! own text comes indented by two spaces:
+//!
+//! ```text
+//! board 0 green:
+//!   I0-+1-O0
+//!
+//!   data 0:
+//! ```
+//!
+//! Icons are `green`, `red`, `blue` or `other`: custom icons don't fit in text,
+//! and come back empty.
+//!
+//! Connections are timestamped in reading order when parsed. Orientations are
+//! taken from the text as is, and tiles aren't checked against their
+//! connections.
+
+use crate::{Board, BoardIcon, DataId, Orientation, SubBoardId, Tile};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    InvalidTile {
+        line: usize,
+        cell: String,
+    },
+    InvalidConnection {
+        line: usize,
+        character: char,
+    },
+    /// A blank line opening the grid, or a line after the grid that isn't a
+    /// data or sub-board description.
+    InvalidLine {
+        line: usize,
+    },
+    /// Sub-boards and data have to be described in order, starting from 0.
+    UnexpectedId {
+        line: usize,
+    },
+    /// A tile refers to a sub-board or data that isn't described.
+    MissingId {
+        id: char,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::InvalidTile { line, cell } => {
+                write!(f, "line {}: invalid tile `{}`", line, cell)
+            }
+            ParseError::InvalidConnection { line, character } => {
+                write!(f, "line {}: invalid connection `{}`", line, character)
+            }
+            ParseError::InvalidLine { line } => write!(f, "line {}: invalid line", line),
+            ParseError::UnexpectedId { line } => write!(f, "line {}: out of order id", line),
+            ParseError::MissingId { id } => write!(f, "no sub-board or data {}", id),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A board holds more sub-boards or data than the text format can number:
+/// ids are single base 36 digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooManyIds;
+
+impl fmt::Display for TooManyIds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "too many sub-boards or data to draw as text")
+    }
+}
+
+impl std::error::Error for TooManyIds {}
+
+fn arrow(orientation: Orientation) -> char {
+    match orientation {
+        Orientation::North => '^',
+        Orientation::East => '>',
+        Orientation::South => 'v',
+        Orientation::West => '<',
+    }
+}
+
+fn from_arrow(arrow: char) -> Option<Orientation> {
+    match arrow {
+        '^' => Some(Orientation::North),
+        '>' => Some(Orientation::East),
+        'v' => Some(Orientation::South),
+        '<' => Some(Orientation::West),
+        _ => None,
+    }
+}
+
+/// Formats an id as a single base 36 digit.
+fn id_char(id: u32) -> Result<char, TooManyIds> {
+    std::char::from_digit(id, 36).ok_or(TooManyIds)
+}
+
+fn cell(tile: &Tile) -> Result<[char; 2], TooManyIds> {
+    let flag = |reversed: bool, normal: char| {
+        if reversed {
+            normal.to_ascii_lowercase()
+        } else {
+            normal
+        }
+    };
+    let cell = match *tile {
+        Tile::Empty => ['.', ' '],
+        Tile::Unusable { broken } => [if broken { 'X' } else { 'x' }, ' '],
+        Tile::Wire { slow } => [if slow { '%' } else { '*' }, ' '],
+        Tile::Bridge => ['#', ' '],
+        Tile::Joiner { orientation } => ['J', arrow(orientation)],
+        Tile::Cloner => ['C', ' '],
+        Tile::Sorter {
+            orientation,
+            reversed,
+        } => [flag(reversed, 'S'), arrow(orientation)],
+        Tile::Deleter => ['D', ' '],
+        Tile::Flipflop {
+            orientation,
+            reversed,
+        } => [flag(reversed, 'F'), arrow(orientation)],
+        Tile::Incrementer { reversed } => [if reversed { '-' } else { '+' }, '1'],
+        Tile::Button { orientation } => ['B', arrow(orientation)],
+        Tile::Lock { locked } => [flag(!locked, 'L'), ' '],
+        Tile::SubBoard { contents } => ['@', id_char(contents.0)?],
+        Tile::Input { data } => ['I', id_char(data.0)?],
+        Tile::Output { expected_data } => ['O', id_char(expected_data.0)?],
+    };
+    Ok(cell)
+}
+
+/// Parses a cell, without checking that the ids it refers to exist.
+fn parse_cell(cell: [char; 2]) -> Option<Tile> {
+    let id = || cell[1].to_digit(36);
+    let tile = match cell {
+        ['.', ' '] => Tile::Empty,
+        ['x', ' '] | ['X', ' '] => Tile::Unusable {
+            broken: cell[0] == 'X',
+        },
+        ['*', ' '] | ['%', ' '] => Tile::Wire {
+            slow: cell[0] == '%',
+        },
+        ['#', ' '] => Tile::Bridge,
+        ['J', arrow] => Tile::Joiner {
+            orientation: from_arrow(arrow)?,
+        },
+        ['C', ' '] => Tile::Cloner,
+        ['S', arrow] | ['s', arrow] => Tile::Sorter {
+            orientation: from_arrow(arrow)?,
+            reversed: cell[0] == 's',
+        },
+        ['D', ' '] => Tile::Deleter,
+        ['F', arrow] | ['f', arrow] => Tile::Flipflop {
+            orientation: from_arrow(arrow)?,
+            reversed: cell[0] == 'f',
+        },
+        ['+', '1'] | ['-', '1'] => Tile::Incrementer {
+            reversed: cell[0] == '-',
+        },
+        ['B', arrow] => Tile::Button {
+            orientation: from_arrow(arrow)?,
+        },
+        ['L', ' '] | ['l', ' '] => Tile::Lock {
+            locked: cell[0] == 'L',
+        },
+        ['@', _] => Tile::SubBoard {
+            contents: SubBoardId(id()?),
+        },
+        ['I', _] => Tile::Input {
+            data: DataId(id()?),
+        },
+        ['O', _] => Tile::Output {
+            expected_data: DataId(id()?),
+        },
+        _ => return None,
+    };
+    Some(tile)
+}
+
+impl Board {
+    /// Draws the board in the text format described in [`crate::text`].
+    /// Fails if it holds more sub-boards or data than the format can number.
+    pub fn to_text(&self) -> Result<String, TooManyIds> {
+        let mut out = String::new();
+        for y in 0..self.height {
+            let mut line = String::new();
+            for x in 0..self.width {
+                line.extend(&cell(self.get_tile(x, y).unwrap())?);
+                if x + 1 < self.width {
+                    let connected = self.is_connected(x, y, Orientation::East);
+                    line.push(if connected { '-' } else { ' ' });
+                }
+            }
+            push_line(&mut out, &line);
+
+            if y + 1 < self.height {
+                let mut line = String::new();
+                for x in 0..self.width {
+                    let connected = self.is_connected(x, y, Orientation::South);
+                    line.push_str(if connected { "|  " } else { "   " });
+                }
+                push_line(&mut out, &line);
+            }
+        }
+
+        let store = &self.store;
+        if store.sub_boards.is_empty() && store.data.is_empty() {
+            return Ok(out);
+        }
+        if !store.data.is_empty() {
+            out.push('\n');
+        }
+        for (id, data) in store.data.iter().enumerate() {
+            let mut line = format!("data {}:", id_char(id as u32)?);
+            for value in data.iter() {
+                line.push_str(&format!(" {}", value));
+            }
+            push_line(&mut out, &line);
+        }
+        for (id, sub_board) in store.sub_boards.iter().enumerate() {
+            let icon = match sub_board.icon {
+                BoardIcon::Green => "green",
+                BoardIcon::Red => "red",
+                BoardIcon::Blue => "blue",
+                BoardIcon::Other { .. } => "other",
+            };
+            out.push('\n');
+            push_line(
+                &mut out,
+                &format!("board {} {}:", id_char(id as u32)?, icon),
+            );
+            for line in sub_board.contents.to_text()?.lines() {
+                push_line(&mut out, &format!("  {}", line));
+            }
+        }
+        Ok(out)
+    }
+
+    /// Parses a board drawn in the text format described in [`crate::text`].
+    pub fn from_text(text: &str) -> Result<Board, ParseError> {
+        let lines: Vec<&str> = text.lines().collect();
+        parse_board(&lines, 1)
+    }
+}
+
+/// Appends `line` without its trailing spaces.
+fn push_line(out: &mut String, line: &str) {
+    out.push_str(line.trim_end());
+    out.push('\n');
+}
+
+/// Parses the board in `lines`, the first of which is line number `first`.
+pub(crate) fn parse_board(lines: &[&str], first: usize) -> Result<Board, ParseError> {
+    // Lines without vertical connections are blank, so the grid goes on up
+    // to the first description.
+    let mut grid_len = lines
+        .iter()
+        .position(|line| line.starts_with("data ") || line.starts_with("board "))
+        .unwrap_or(lines.len());
+    while grid_len > 0 && lines[grid_len - 1].trim().is_empty() {
+        grid_len -= 1;
+    }
+    // The grid starts with a row of tiles: a blank line there would turn the
+    // rows after it into connections.
+    if grid_len > 0 && lines[0].trim().is_empty() {
+        return Err(ParseError::InvalidLine { line: first });
+    }
+    let grid: Vec<Vec<char>> = lines[..grid_len]
+        .iter()
+        .map(|line| line.chars().collect())
+        .collect();
+    let height = (grid.len() + 1) / 2;
+    let width = grid
+        .iter()
+        .step_by(2)
+        .map(|line| (line.len() + 2) / 3)
+        .max()
+        .unwrap_or(0);
+    let mut board = Board::new(width, height);
+    let at = |line: &Vec<char>, column: usize| line.get(column).copied().unwrap_or(' ');
+
+    for (row, line) in grid.iter().enumerate() {
+        let line_number = first + row;
+        let y = row / 2;
+        for x in 0..width {
+            let column = x * 3;
+            if row % 2 == 0 {
+                let chars = [at(line, column), at(line, column + 1)];
+                let tile = parse_cell(chars).ok_or_else(|| ParseError::InvalidTile {
+                    line: line_number,
+                    cell: chars.iter().collect(),
+                })?;
+                *board.tiles.get_mut(x, y).unwrap() = tile;
+            }
+
+            let (side, link, rest) = if row % 2 == 0 {
+                (Orientation::East, at(line, column + 2), ' ')
+            } else {
+                let rest = at(line, column + 1).max(at(line, column + 2));
+                (Orientation::South, at(line, column), rest)
+            };
+            let expected = if side == Orientation::East { '-' } else { '|' };
+            let invalid = |character| ParseError::InvalidConnection {
+                line: line_number,
+                character,
+            };
+            if rest != ' ' {
+                return Err(invalid(rest));
+            }
+            if link == ' ' {
+                continue;
+            }
+            let timestamp = board.connection_counter;
+            match board.get_mut_connection(x, y, side) {
+                Some(conn) if link == expected => {
+                    conn.is_connected = true;
+                    conn.timestamp = timestamp;
+                }
+                _ => return Err(invalid(link)),
+            }
+            board.connection_counter += 1;
+        }
+        // Only connection lines can reach past the last tile.
+        if let Some(&character) = line.iter().skip(width * 3).find(|&&c| c != ' ') {
+            return Err(ParseError::InvalidConnection {
+                line: line_number,
+                character,
+            });
+        }
+    }
+
+    parse_store(&mut board, &lines[grid_len..], first + grid_len)?;
+
+    for (_, _, tile) in &board.tiles {
+        let id = match *tile {
+            Tile::SubBoard { contents } if contents.0 as usize >= board.store.sub_boards.len() => {
+                contents.0
+            }
+            Tile::Input { data: id } | Tile::Output { expected_data: id }
+                if id.0 as usize >= board.store.data.len() =>
+            {
+                id.0
+            }
+            _ => continue,
+        };
+        let id = id_char(id).expect("parsed ids fit in a digit");
+        return Err(ParseError::MissingId { id });
+    }
+    Ok(board)
+}
+
+/// Parses the sub-boards and data described after a board's grid.
+fn parse_store(board: &mut Board, lines: &[&str], first: usize) -> Result<(), ParseError> {
+    let mut row = 0;
+    while row < lines.len() {
+        let line_number = first + row;
+        let line = lines[row];
+        row += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = ParseError::InvalidLine { line: line_number };
+        let (header, rest) = match line.find(':') {
+            Some(colon) => (&line[..colon], &line[colon + 1..]),
+            None => return Err(invalid),
+        };
+        let words: Vec<&str> = header.split(' ').collect();
+        let id = |word: &str, count: usize| match u32::from_str_radix(word, 36) {
+            Ok(id) if id as usize == count => Ok(()),
+            Ok(_) => Err(ParseError::UnexpectedId { line: line_number }),
+            Err(_) => Err(ParseError::InvalidLine { line: line_number }),
+        };
+
+        match words.as_slice() {
+            ["data", word] => {
+                id(word, board.store.data.len())?;
+                let data = rest
+                    .split_whitespace()
+                    .map(u32::from_str)
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| invalid)?;
+                board.store.add_data(data);
+            }
+            ["board", word, icon] if rest.trim().is_empty() => {
+                id(word, board.store.sub_boards.len())?;
+                let icon = match *icon {
+                    "green" => BoardIcon::Green,
+                    "red" => BoardIcon::Red,
+                    "blue" => BoardIcon::Blue,
+                    "other" => BoardIcon::Other { png_data: vec![] },
+                    _ => return Err(invalid),
+                };
+                let start = row;
+                while row < lines.len() && (lines[row].starts_with("  ") || lines[row].is_empty()) {
+                    row += 1;
+                }
+                let mut end = row;
+                while end > start && lines[end - 1].is_empty() {
+                    end -= 1;
+                }
+                let nested: Vec<&str> = lines[start..end]
+                    .iter()
+                    .map(|l| l.get(2..).unwrap_or(""))
+                    .collect();
+                let contents = parse_board(&nested, first + start)?;
+                board.store.add_sub_board(contents, icon);
+            }
+            _ => return Err(invalid),
+        }
+    }
+    Ok(())
+}
+
+impl FromStr for Board {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Board, ParseError> {
+        Board::from_text(text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Game, Verdict};
+
+    /// The filter board of the game tests: zeroes get sorted into the
+    /// deleter, everything else goes on to the output.
+    const FILTER: &str = "\
+I0-* -S<-* -O1
+      |
+.  .  D  .  .
+
+data 0: 0 3 0 5
+data 1: 3 5
+";
+
+    #[test]
+    fn draws_board() {
+        let mut board = Board::new(5, 2);
+        let data = board.store_mut().add_data(vec![0, 3, 0, 5]);
+        let expected_data = board.store_mut().add_data(vec![3, 5]);
+        board.set_tile(0, 0, Tile::Input { data });
+        board.set_tile(1, 0, Tile::Wire { slow: false });
+        board.set_tile(
+            2,
+            0,
+            Tile::Sorter {
+                orientation: Orientation::West,
+                reversed: false,
+            },
+        );
+        board.set_tile(2, 1, Tile::Deleter);
+        board.set_tile(3, 0, Tile::Wire { slow: false });
+        board.set_tile(4, 0, Tile::Output { expected_data });
+        board.connect(0, 0, Orientation::East);
+        board.connect(1, 0, Orientation::East);
+        board.connect(2, 0, Orientation::South);
+        board.connect(2, 0, Orientation::East);
+        board.connect(3, 0, Orientation::East);
+        assert_eq!(board.to_text().unwrap(), FILTER);
+    }
+
+    #[test]
+    fn parsed_board_runs() {
+        let board: Board = FILTER.parse().unwrap();
+        let mut game = Game::with(board);
+        assert_eq!(game.run_to_completion(100), Verdict::Pass);
+        assert_eq!(game.received(4, 0), Some(&[3, 5][..]));
+    }
+
+    #[test]
+    fn round_trip() {
+        let text = "\
+X -x -* -% -#
+|           |
+J>-C  S^ s< D
+|  |  |
+F>-fv +1 -1-Bv
+            |
+L -l  @0-I0 O1
+
+data 0: 1 2 3
+data 1: 4294967295
+
+board 0 red:
+  I0-+1-O0
+  |
+  .  .  .
+
+  data 0:
+";
+        let board = Board::from_text(text).unwrap();
+        assert_eq!(board.to_text().unwrap(), text);
+        assert_eq!(board.get_tile(1, 1), Some(&Tile::Cloner),);
+        assert!(board.is_connected(4, 0, Orientation::South));
+        assert!(!board.is_connected(1, 1, Orientation::East));
+        let inner = &board.store().sub_board(SubBoardId(0)).unwrap().contents;
+        assert_eq!(
+            inner.get_tile(1, 0),
+            Some(&Tile::Incrementer { reversed: false })
+        );
+        assert!(inner.is_connected(0, 0, Orientation::South));
+    }
+
+    #[test]
+    fn many_ids() {
+        // Shared data keeps the ids few however many tiles use them.
+        let mut board = Board::new(40, 2);
+        for x in 0..40 {
+            let data = board.store_mut().share_data(&[x as u32 % 2]);
+            board.set_tile(x, 0, Tile::Input { data });
+            let expected_data = board.store_mut().share_data(&[]);
+            board.set_tile(x, 1, Tile::Output { expected_data });
+        }
+        let text = board.to_text().unwrap();
+        assert_eq!(Board::from_text(&text).unwrap(), board);
+
+        // Past 36, ids don't fit in a digit.
+        for x in 0..37 {
+            let data = board.store_mut().add_data(vec![x]);
+            board.set_tile(x as usize, 0, Tile::Input { data });
+        }
+        assert_eq!(board.to_text(), Err(TooManyIds));
+    }
+
+    #[test]
+    fn connections_are_timestamped_in_reading_order() {
+        // Blank lines in the grid are rows without vertical connections.
+        let mut board = Board::from_text(".  *  .\n\n* -C -*\n   |\n.  *  .").unwrap();
+        // The cloner can't hold a fourth connection: the oldest, west, goes.
+        board.connect(1, 1, Orientation::North);
+        assert_eq!(board.get_connections(1, 1), [true, true, true, false]);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Board::from_text("* -Q"),
+            Err(ParseError::InvalidTile {
+                line: 1,
+                cell: "Q ".to_string()
+            })
+        );
+        assert_eq!(
+            Board::from_text("* |*"),
+            Err(ParseError::InvalidConnection {
+                line: 1,
+                character: '|'
+            })
+        );
+        assert_eq!(
+            Board::from_text("* \n -"),
+            Err(ParseError::InvalidConnection {
+                line: 2,
+                character: '-'
+            })
+        );
+        assert_eq!(
+            Board::from_text("* -"),
+            Err(ParseError::InvalidConnection {
+                line: 1,
+                character: '-'
+            })
+        );
+        assert_eq!(
+            Board::from_text("*\n   |"),
+            Err(ParseError::InvalidConnection {
+                line: 2,
+                character: '|'
+            })
+        );
+        assert_eq!(
+            Board::from_text("\n* -*"),
+            Err(ParseError::InvalidLine { line: 1 })
+        );
+        assert_eq!(
+            Board::from_text("I0\n\ndata 1: 2"),
+            Err(ParseError::UnexpectedId { line: 3 })
+        );
+        assert_eq!(
+            Board::from_text("I0\n\ndata 0: two"),
+            Err(ParseError::InvalidLine { line: 3 })
+        );
+        assert_eq!(
+            Board::from_text("@0"),
+            Err(ParseError::MissingId { id: '0' })
+        );
+    }
+}
@@ -0,0 +1,482 @@
+//! What happens during a game, tick by tick, for debugging and comparing
+//! designs. See [`crate::Game::step_traced`].
+
+use crate::{Orientation, TileKind};
+use std::io::{self, Write};
+
+/// Which way a [`Tile::Sorter`](crate::Tile::Sorter) sent a signal, from
+/// the signal's point of view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Turn {
+    Left,
+    Straight,
+    Right,
+}
+
+impl Turn {
+    /// The turn a signal travelling towards `heading` takes to leave through
+    /// `exit`.
+    pub(crate) fn between(heading: Orientation, exit: Orientation) -> Turn {
+        if exit == heading.counter_clockwise() {
+            Turn::Left
+        } else if exit == heading.clockwise() {
+            Turn::Right
+        } else {
+            Turn::Straight
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Turn::Left => "left",
+            Turn::Straight => "straight",
+            Turn::Right => "right",
+        }
+    }
+}
+
+/// Something that happened on the board during `tick`. Positions are those
+/// of tiles on the board the event happened on: events inside sub-boards
+/// come wrapped in [`Event::InSubBoard`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// An input sent a value, or one left a sub-board.
+    Spawned {
+        tick: u64,
+        at: (usize, usize),
+        value: u32,
+    },
+    Moved {
+        tick: u64,
+        from: (usize, usize),
+        to: (usize, usize),
+        value: u32,
+    },
+    /// A flipflop flipped, a button got pressed, a lock let a signal through
+    /// or an incrementer changed a value.
+    Activated {
+        tick: u64,
+        at: (usize, usize),
+        tile: TileKind,
+    },
+    Cloned {
+        tick: u64,
+        at: (usize, usize),
+        value: u32,
+    },
+    /// A signal disappeared: consumed by a tile, fallen off the board
+    /// through a side that isn't connected, or lost on the way into or out
+    /// of a sub-board without a port there.
+    Deleted {
+        tick: u64,
+        at: (usize, usize),
+        value: u32,
+    },
+    Sorted {
+        tick: u64,
+        at: (usize, usize),
+        value: u32,
+        turn: Turn,
+    },
+    OutputMatched {
+        tick: u64,
+        at: (usize, usize),
+        value: u32,
+    },
+    /// An output received `value` where it expected `expected`, or nothing
+    /// more.
+    OutputMismatched {
+        tick: u64,
+        at: (usize, usize),
+        value: u32,
+        expected: Option<u32>,
+    },
+    /// Something that happened inside the sub-board at `at`.
+    InSubBoard {
+        at: (usize, usize),
+        event: Box<Event>,
+    },
+}
+
+impl Event {
+    pub fn tick(&self) -> u64 {
+        match *self {
+            Event::Spawned { tick, .. }
+            | Event::Moved { tick, .. }
+            | Event::Activated { tick, .. }
+            | Event::Cloned { tick, .. }
+            | Event::Deleted { tick, .. }
+            | Event::Sorted { tick, .. }
+            | Event::OutputMatched { tick, .. }
+            | Event::OutputMismatched { tick, .. } => tick,
+            Event::InSubBoard { ref event, .. } => event.tick(),
+        }
+    }
+
+    /// Formats the event as a single line JSON object, with its fields in a
+    /// fixed order so that traces can be diffed. Events inside sub-boards
+    /// get a `path` field, listing the sub-board tiles they are in from the
+    /// outermost one.
+    pub fn to_json(&self) -> String {
+        let pos = |(x, y): (usize, usize)| format!("[{},{}]", x, y);
+        let mut path = Vec::new();
+        let mut event = self;
+        while let Event::InSubBoard { at, event: inner } = event {
+            path.push(pos(*at));
+            event = inner;
+        }
+        let (name, fields) = match event {
+            Event::Spawned { at, value, .. } => {
+                ("spawned", format!(r#""at":{},"value":{}"#, pos(*at), value))
+            }
+            Event::Moved {
+                from, to, value, ..
+            } => (
+                "moved",
+                format!(
+                    r#""from":{},"to":{},"value":{}"#,
+                    pos(*from),
+                    pos(*to),
+                    value
+                ),
+            ),
+            Event::Activated { at, tile, .. } => (
+                "activated",
+                format!(r#""at":{},"tile":"{}""#, pos(*at), tile.name()),
+            ),
+            Event::Cloned { at, value, .. } => {
+                ("cloned", format!(r#""at":{},"value":{}"#, pos(*at), value))
+            }
+            Event::Deleted { at, value, .. } => {
+                ("deleted", format!(r#""at":{},"value":{}"#, pos(*at), value))
+            }
+            Event::Sorted {
+                at, value, turn, ..
+            } => (
+                "sorted",
+                format!(
+                    r#""at":{},"value":{},"turn":"{}""#,
+                    pos(*at),
+                    value,
+                    turn.name()
+                ),
+            ),
+            Event::OutputMatched { at, value, .. } => (
+                "output-matched",
+                format!(r#""at":{},"value":{}"#, pos(*at), value),
+            ),
+            Event::OutputMismatched {
+                at,
+                value,
+                expected,
+                ..
+            } => (
+                "output-mismatched",
+                format!(
+                    r#""at":{},"value":{},"expected":{}"#,
+                    pos(*at),
+                    value,
+                    expected.map_or("null".to_string(), |e| e.to_string())
+                ),
+            ),
+            Event::InSubBoard { .. } => unreachable!("sub-board events are unwrapped"),
+        };
+        let path = if path.is_empty() {
+            String::new()
+        } else {
+            format!(r#""path":[{}],"#, path.join(","))
+        };
+        format!(
+            r#"{{"tick":{},"event":"{}",{}{}}}"#,
+            self.tick(),
+            name,
+            path,
+            fields
+        )
+    }
+}
+
+/// Receives the events of a game as it runs.
+pub trait EventSink {
+    fn record(&mut self, event: Event);
+}
+
+/// Ignores every event.
+impl EventSink for () {
+    fn record(&mut self, _event: Event) {}
+}
+
+/// Collects every event.
+impl EventSink for Vec<Event> {
+    fn record(&mut self, event: Event) {
+        self.push(event);
+    }
+}
+
+/// Passes the events of a sub-board on to the sink of the board holding it.
+pub(crate) struct SubBoardSink<'a> {
+    pub sink: &'a mut dyn EventSink,
+    /// The position of the sub-board tile.
+    pub at: (usize, usize),
+}
+
+impl EventSink for SubBoardSink<'_> {
+    fn record(&mut self, event: Event) {
+        self.sink.record(Event::InSubBoard {
+            at: self.at,
+            event: Box::new(event),
+        });
+    }
+}
+
+/// Writes events as JSON Lines, one [`Event::to_json`] object per line.
+#[derive(Debug)]
+pub struct JsonLines<W: Write> {
+    writer: W,
+    /// The first write that failed. Events after it are dropped.
+    error: Option<io::Error>,
+}
+
+impl<W: Write> JsonLines<W> {
+    pub fn new(writer: W) -> JsonLines<W> {
+        JsonLines {
+            writer,
+            error: None,
+        }
+    }
+
+    /// Flushes the writer and hands it back, or the first error writing to
+    /// it caused.
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> EventSink for JsonLines<W> {
+    fn record(&mut self, event: Event) {
+        if self.error.is_none() {
+            if let Err(error) = writeln!(self.writer, "{}", event.to_json()) {
+                self.error = Some(error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Board, Game};
+
+    #[test]
+    fn turns() {
+        use Orientation::*;
+        assert_eq!(Turn::between(North, West), Turn::Left);
+        assert_eq!(Turn::between(North, East), Turn::Right);
+        assert_eq!(Turn::between(East, North), Turn::Left);
+        assert_eq!(Turn::between(South, South), Turn::Straight);
+    }
+
+    /// Zeroes go to the deleter below the sorter, everything else gets
+    /// incremented on its way to the output. The output expects a 5 it won't
+    /// get.
+    const FILTER: &str = "\
+I0-S<-+1-* -O1
+   |
+.  D  .  .  .
+
+data 0: 0 3 1
+data 1: 4 5
+";
+
+    fn trace(text: &str) -> Vec<Event> {
+        let mut game = Game::with(Board::from_text(text).unwrap());
+        let mut events = Vec::new();
+        while game.outcome(100).is_none() {
+            game.step_traced(&mut events);
+        }
+        events
+    }
+
+    #[test]
+    fn events() {
+        let events = trace(FILTER);
+        let first_value: Vec<_> = events.iter().take_while(|e| e.tick() <= 2).collect();
+        assert_eq!(
+            first_value,
+            [
+                &Event::Spawned {
+                    tick: 1,
+                    at: (0, 0),
+                    value: 0
+                },
+                &Event::Moved {
+                    tick: 1,
+                    from: (0, 0),
+                    to: (1, 0),
+                    value: 0
+                },
+                &Event::Sorted {
+                    tick: 1,
+                    at: (1, 0),
+                    value: 0,
+                    turn: Turn::Right
+                },
+                &Event::Moved {
+                    tick: 2,
+                    from: (1, 0),
+                    to: (1, 1),
+                    value: 0
+                },
+                &Event::Deleted {
+                    tick: 2,
+                    at: (1, 1),
+                    value: 0
+                },
+            ]
+        );
+        assert!(events.contains(&Event::Sorted {
+            tick: 3,
+            at: (1, 0),
+            value: 3,
+            turn: Turn::Straight
+        }));
+        assert!(events.contains(&Event::Activated {
+            tick: 4,
+            at: (2, 0),
+            tile: TileKind::Incrementer
+        }));
+        assert!(events.contains(&Event::OutputMatched {
+            tick: 6,
+            at: (4, 0),
+            value: 4
+        }));
+        assert_eq!(
+            events.last(),
+            Some(&Event::OutputMismatched {
+                tick: 10,
+                at: (4, 0),
+                value: 2,
+                expected: Some(5)
+            })
+        );
+    }
+
+    #[test]
+    fn clones_and_deletes() {
+        let events = trace("I0-C -D\n   |\n.  *  .\n\ndata 0: 7");
+        let kinds: Vec<_> = events
+            .iter()
+            .filter(|e| !matches!(e, Event::Moved { .. } | Event::Spawned { .. }))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                &Event::Cloned {
+                    tick: 1,
+                    at: (1, 0),
+                    value: 7
+                },
+                // One copy gets deleted, the other one goes south into the
+                // wire's dead end.
+                &Event::Deleted {
+                    tick: 2,
+                    at: (2, 0),
+                    value: 7
+                },
+                &Event::Deleted {
+                    tick: 2,
+                    at: (1, 1),
+                    value: 7
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn json_lines() {
+        let mut sink = JsonLines::new(Vec::new());
+        let mut game = Game::with(Board::from_text(FILTER).unwrap());
+        for _ in 0..4 {
+            game.step_traced(&mut sink);
+        }
+        let json = String::from_utf8(sink.finish().unwrap()).unwrap();
+        let lines: Vec<_> = json.lines().collect();
+        assert_eq!(lines.len(), 10);
+        assert_eq!(
+            lines[0],
+            r#"{"tick":1,"event":"spawned","at":[0,0],"value":0}"#
+        );
+        assert_eq!(
+            lines[2],
+            r#"{"tick":1,"event":"sorted","at":[1,0],"value":0,"turn":"right"}"#
+        );
+        assert_eq!(
+            lines[4],
+            r#"{"tick":2,"event":"deleted","at":[1,1],"value":0}"#
+        );
+        assert_eq!(
+            lines[9],
+            r#"{"tick":4,"event":"activated","at":[2,0],"tile":"incrementer"}"#
+        );
+        assert_eq!(
+            Event::OutputMismatched {
+                tick: 9,
+                at: (1, 2),
+                value: 3,
+                expected: None
+            }
+            .to_json(),
+            r#"{"tick":9,"event":"output-mismatched","at":[1,2],"value":3,"expected":null}"#
+        );
+    }
+
+    /// An incrementer inside a sub-board.
+    const SUB_BOARD: &str = "\
+.  O1
+   |
+I0-@0
+
+data 0: 1
+data 1: 2
+
+board 0 green:
+  .  O0
+     |
+  I0-+1
+
+  data 0:
+";
+
+    #[test]
+    fn sub_board_events() {
+        let events = trace(SUB_BOARD);
+        let inside = Event::InSubBoard {
+            at: (1, 1),
+            event: Box::new(Event::Activated {
+                tick: 2,
+                at: (1, 1),
+                tile: TileKind::Incrementer,
+            }),
+        };
+        assert!(events.contains(&inside));
+        assert_eq!(inside.tick(), 2);
+        assert_eq!(
+            inside.to_json(),
+            r#"{"tick":2,"event":"activated","path":[[1,1]],"at":[1,1],"tile":"incrementer"}"#
+        );
+        assert!(events.contains(&Event::Spawned {
+            tick: 3,
+            at: (1, 1),
+            value: 2
+        }));
+    }
+
+    #[test]
+    fn traces_are_reproducible() {
+        assert_eq!(trace(FILTER), trace(FILTER));
+    }
+}